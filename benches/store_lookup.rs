@@ -0,0 +1,77 @@
+//! Сравнивает выдачу аудитории с картинками из MemoryStore (поиск по HashMap)
+//! с линейным перебором тех же данных в памяти
+//!
+//! Запуск: cargo bench --bench store_lookup
+//!
+//! Обе стороны делают одну и ту же работу, кроме поиска: копируют только найденные
+//! аудиторию и картинки и собирают из них JSON. Выдача из MemoryStore идет через
+//! server::with_inline_images, как в обработчике /classroom. Монго здесь не участвует,
+//! поэтому на индексы и проекции DBClient этот замер не указывает
+
+use actix_navigation_service::memory_store::MemoryStore;
+use actix_navigation_service::models::{parse_classroom_data, CampusImage, ClassroomData};
+use actix_navigation_service::server::with_inline_images;
+use actix_navigation_service::store::NavigationStore;
+use futures::executor::block_on;
+use std::collections::BTreeSet;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+/// Размер одной картинки в Base64, примерно как фото корпуса
+const IMAGE_SIZE: usize = 48 * 1024;
+const ITERATIONS: usize = 200;
+
+/// Картинки для всех названий, на которые ссылается classrooms.json
+fn synthetic_images(classrooms: &[ClassroomData]) -> Vec<CampusImage> {
+    let names: BTreeSet<&String> = classrooms.iter().flat_map(|x|x.images.iter()).collect();
+    names.into_iter()
+        .enumerate()
+        .map(|(i, name)|CampusImage{
+            name: name.to_owned(),
+            value: ((b'A' + (i % 26) as u8) as char).to_string().repeat(IMAGE_SIZE)})
+        .collect()
+}
+
+/// Выдача аудитории с картинками линейным перебором списков
+fn scan_lookup(classrooms: &[ClassroomData], images: &[CampusImage], name: &str) -> Option<String> {
+    let mut classroom = classrooms.iter().find(|x|x.classroom == name)?.clone();
+    classroom.images = classroom.images
+        .iter()
+        .filter_map(|image_name|images.iter().find(|image|&image.name == image_name))
+        .map(|image|image.value.to_owned())
+        .collect();
+    serde_json::to_string(&classroom).ok()
+}
+
+fn measure(mut lookup: impl FnMut(&str), names: &[String]) -> Duration {
+    let started = Instant::now();
+    for i in 0..ITERATIONS {
+        lookup(&names[i % names.len()]);
+    }
+    started.elapsed() / ITERATIONS as u32
+}
+
+fn main() {
+    let classroom_data = std::fs::read_to_string("classrooms.json").expect("No classrooms.json");
+    let classrooms = parse_classroom_data(&classroom_data).expect("Bad classrooms.json");
+    let images = synthetic_images(&classrooms);
+    let image_data = serde_json::to_string(&images).unwrap();
+    let store = MemoryStore::new(classroom_data, image_data).expect("Store init failed");
+    let names: Vec<String> = classrooms.iter()
+        .filter(|x|!x.images.is_empty())
+        .map(|x|x.classroom.to_owned())
+        .collect();
+
+    let scan = measure(|name|{black_box(classrooms.iter().find(|x|x.classroom == name).cloned());}, &names);
+    let indexed = measure(|name|{black_box(block_on(store.get_classroom(name.to_owned())).ok());}, &names);
+    let scan_with_images = measure(|name|{black_box(scan_lookup(&classrooms, &images, name));}, &names);
+    let indexed_with_images = measure(|name|{
+        black_box(block_on(with_inline_images(&store, name)).ok().and_then(|x|serde_json::to_string(&x).ok()));
+    }, &names);
+
+    println!("{} classrooms, {} images of {} KiB, {} lookups", classrooms.len(), images.len(), IMAGE_SIZE / 1024, ITERATIONS);
+    println!("classroom, linear scan:            {:>10.3?} per lookup", scan);
+    println!("classroom, MemoryStore (HashMap):  {:>10.3?} per lookup", indexed);
+    println!("with images, linear scan:          {:>10.3?} per lookup", scan_with_images);
+    println!("with images, MemoryStore:          {:>10.3?} per lookup", indexed_with_images);
+}
@@ -0,0 +1,21 @@
+//! Передает в сборку хэш коммита для /version
+//!
+//! Берется из переменной GIT_HASH (в Docker .git нет, хэш передается аргументом сборки),
+//! а если ее нет - из git. Без того и другого - "unknown"
+
+use std::process::Command;
+
+fn main() {
+    println!("cargo:rerun-if-env-changed=GIT_HASH");
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs/heads");
+    let git_hash = std::env::var("GIT_HASH")
+        .ok()
+        .filter(|x|!x.is_empty())
+        .or_else(||{
+            let output = Command::new("git").args(["rev-parse", "--short", "HEAD"]).output().ok()?;
+            output.status.success().then(||String::from_utf8_lossy(&output.stdout).trim().to_owned())
+        })
+        .unwrap_or_else(||"unknown".to_owned());
+    println!("cargo:rustc-env=GIT_HASH={}", git_hash);
+}
@@ -0,0 +1,528 @@
+use actix_service::Service;
+use actix_web::body::BoxBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::{header, StatusCode};
+use actix_web::{delete, get, patch, post, put, HttpRequest, HttpResponse, ResponseError, web};
+use serde_json::json;
+use std::future::Future;
+use super::cache::ResponseCache;
+use super::error::{ApiError, StoreError};
+use super::floor_plan;
+use super::images::ImageResource;
+use super::location::ClassroomLocation;
+use super::maps::MapCatalog;
+use super::models::{CampusImage, ClassroomData, RoomPlan};
+use super::reload::Reloader;
+use super::server::SharedStore;
+use super::store::NavigationStore;
+
+/// Токен для админских запросов, передается в заголовке "Authorization: Bearer <токен>"
+///
+/// # Примечание:
+/// Если токен не задан, админские запросы отклоняются
+#[derive(Debug, Clone, Default)]
+pub struct AdminToken(pub Option<String>);
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct ClassroomPatch{
+    classroom: Option<String>,
+    images: Option<Vec<String>>,
+    description: Option<String>,
+    plan: Option<RoomPlan>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct ImagePatch{
+    value: String,
+}
+
+/// Регистрирует админские обработчики в /admin
+///
+/// # Примечание:
+/// Токен проверяет require_token до вызова обработчиков, то есть до разбора пути и тела
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::scope("/admin")
+        .wrap_fn(require_token)
+        .service(create_classroom)
+        .service(replace_classroom)
+        .service(patch_classroom)
+        .service(delete_classroom)
+        .service(create_image)
+        .service(replace_image)
+        .service(patch_image)
+        .service(delete_image)
+        .service(reload_data)
+        .service(get_cache_stats)
+        .service(clear_cache));
+}
+
+/// Middleware для Scope::wrap_fn: пропускает к обработчикам только запросы с верным токеном
+///
+/// # Примечание:
+/// Без токена клиент получает 401/403, даже если тело запроса не подходит под схему,
+/// и не узнает схему из ответа 400
+pub fn require_token<S, B>(request: ServiceRequest, service: &S) -> impl Future<Output = Result<ServiceResponse<BoxBody>, actix_web::Error>>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: actix_web::body::MessageBody + 'static,
+{
+    let token = request.app_data::<web::Data<AdminToken>>()
+        .map(|x|x.get_ref().clone())
+        .unwrap_or_default();
+    let call = match authorize(request.request(), &token) {
+        Ok(()) => Ok(service.call(request)),
+        Err(e) => Err((request.into_parts().0, e)),
+    };
+    async move {
+        match call {
+            Ok(response) => Ok(response.await?.map_into_boxed_body()),
+            Err((request, e)) => Ok(ServiceResponse::new(request, e.error_response())),
+        }
+    }
+}
+
+#[post("/classrooms")]
+async fn create_classroom(body: web::Json<ClassroomData>, db_client: SharedStore, maps: web::Data<MapCatalog>) -> Result<HttpResponse, ApiError> {
+    let classroom = body.into_inner();
+    validate_classroom(db_client.get_ref(), &maps, &classroom).await?;
+    db_client.insert_classroom(classroom.clone()).await?;
+    classroom_response(db_client.get_ref(), classroom.classroom, StatusCode::CREATED).await
+}
+
+#[put("/classrooms/{name}")]
+async fn replace_classroom(path: web::Path<String>, body: web::Json<ClassroomData>, db_client: SharedStore, maps: web::Data<MapCatalog>) -> Result<HttpResponse, ApiError> {
+    let classroom = body.into_inner();
+    validate_classroom(db_client.get_ref(), &maps, &classroom).await?;
+    db_client.replace_classroom(path.into_inner(), classroom.clone()).await?;
+    classroom_response(db_client.get_ref(), classroom.classroom, StatusCode::OK).await
+}
+
+#[patch("/classrooms/{name}")]
+async fn patch_classroom(path: web::Path<String>, body: web::Json<ClassroomPatch>, db_client: SharedStore, maps: web::Data<MapCatalog>) -> Result<HttpResponse, ApiError> {
+    let classroom_name = path.into_inner();
+    let patch = body.into_inner();
+    let mut classroom = db_client.get_classroom(classroom_name.to_owned()).await?;
+    if let Some(name) = patch.classroom {
+        classroom.classroom = name;
+    }
+    if let Some(images) = patch.images {
+        classroom.images = images;
+    }
+    if let Some(description) = patch.description {
+        classroom.description = description;
+    }
+    if let Some(plan) = patch.plan {
+        classroom.plan = Some(plan);
+    }
+    validate_classroom(db_client.get_ref(), &maps, &classroom).await?;
+    db_client.replace_classroom(classroom_name, classroom.clone()).await?;
+    classroom_response(db_client.get_ref(), classroom.classroom, StatusCode::OK).await
+}
+
+#[delete("/classrooms/{name}")]
+async fn delete_classroom(path: web::Path<String>, db_client: SharedStore) -> Result<HttpResponse, ApiError> {
+    db_client.delete_classroom(path.into_inner()).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[post("/images")]
+async fn create_image(body: web::Json<CampusImage>, db_client: SharedStore) -> Result<HttpResponse, ApiError> {
+    let image = body.into_inner();
+    validate_image(&image)?;
+    db_client.insert_image(image).await?;
+    Ok(HttpResponse::Created().finish())
+}
+
+#[put("/images/{name}")]
+async fn replace_image(path: web::Path<String>, body: web::Json<CampusImage>, db_client: SharedStore) -> Result<HttpResponse, ApiError> {
+    let image = body.into_inner();
+    if image.name != path.into_inner() {
+        return Err(ApiError::bad_request("image name in body differs from the path, images can not be renamed"));
+    }
+    validate_image(&image)?;
+    db_client.replace_image(image).await?;
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[patch("/images/{name}")]
+async fn patch_image(path: web::Path<String>, body: web::Json<ImagePatch>, db_client: SharedStore) -> Result<HttpResponse, ApiError> {
+    let image = CampusImage{name: path.into_inner(), value: body.into_inner().value};
+    validate_image(&image)?;
+    db_client.replace_image(image).await?;
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[delete("/images/{name}")]
+async fn delete_image(path: web::Path<String>, db_client: SharedStore) -> Result<HttpResponse, ApiError> {
+    let image_name = path.into_inner();
+    let users = db_client.get_classrooms_with_image(image_name.to_owned()).await?;
+    if !users.is_empty() {
+        return Err(ApiError::conflict(format!("image {} is used by classrooms", image_name))
+            .with_details(json!({"classrooms": users})));
+    }
+    db_client.delete_image(image_name).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[post("/reload")]
+async fn reload_data(reloader: Option<web::Data<Reloader>>) -> Result<HttpResponse, ApiError> {
+    let reloader = reloader.ok_or_else(||ApiError::conflict("data files are not configured, nothing to reload"))?;
+    match reloader.reload().await {
+        Ok(status) => Ok(HttpResponse::Ok().json(status)),
+        Err(e) => Err(ApiError::new(StatusCode::UNPROCESSABLE_ENTITY, "invalid_data", "new data rejected, previous dataset kept")
+            .with_details(json!({"reason": e.to_string()}))),
+    }
+}
+
+#[get("/cache")]
+async fn get_cache_stats(cache: Option<web::Data<ResponseCache>>) -> Result<HttpResponse, ApiError> {
+    let cache = cache.ok_or_else(||ApiError::conflict("response cache is disabled"))?;
+    Ok(HttpResponse::Ok().json(cache.stats()))
+}
+
+#[delete("/cache")]
+async fn clear_cache(cache: Option<web::Data<ResponseCache>>) -> Result<HttpResponse, ApiError> {
+    let cache = cache.ok_or_else(||ApiError::conflict("response cache is disabled"))?;
+    cache.invalidate();
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Проверяет токен из заголовка Authorization
+fn authorize(request: &HttpRequest, token: &AdminToken) -> Result<(), ApiError> {
+    let expected = match &token.0 {
+        Some(val) => val,
+        None => return Err(ApiError::new(StatusCode::FORBIDDEN, "forbidden", "admin API is disabled, set ADMIN_TOKEN")),
+    };
+    let provided = request.headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value|value.to_str().ok())
+        .and_then(|value|value.strip_prefix("Bearer "))
+        .unwrap_or_default();
+    // Сравнение без раннего выхода, чтобы не подсказывать токен по времени ответа
+    let matches = provided.len() == expected.len()
+        && provided.bytes().zip(expected.bytes()).fold(0, |acc, (a, b)|acc | (a ^ b)) == 0;
+    if !matches {
+        return Err(ApiError::new(StatusCode::UNAUTHORIZED, "unauthorized", "invalid admin token"));
+    }
+    Ok(())
+}
+
+/// Проверяет, что аудитория названа, ссылается только на существующие картинки
+/// и размечена в пределах своего плана этажа
+///
+/// # Примечание:
+/// Если плана этажа еще нет в maps.json или в хранилище, разметка проверяется только на пустоту
+async fn validate_classroom(db_client: &dyn NavigationStore, maps: &MapCatalog, classroom: &ClassroomData) -> Result<(), ApiError> {
+    if classroom.classroom.trim().is_empty() {
+        return Err(ApiError::bad_request("classroom name is empty"));
+    }
+    if let Some(plan) = &classroom.plan {
+        if plan.points.is_empty() {
+            return Err(ApiError::bad_request("floor plan has no points"));
+        }
+        let location = ClassroomLocation::parse(&classroom.classroom);
+        if let Some(floor_map) = maps.room_plan(plan, &location) {
+            let size = match db_client.get_campus_image(floor_map.image.to_owned()).await {
+                Ok(image) => ImageResource::decode(&image).ok().and_then(|x|floor_plan::plan_size(&x.bytes).ok()),
+                Err(StoreError::ImageNotFound(_)) => None,
+                Err(e) => return Err(e.into()),
+            };
+            if let Some(Err(reason)) = size.map(|size|floor_plan::check_points(plan, size)) {
+                return Err(ApiError::bad_request("classroom marking does not fit the floor plan")
+                    .with_details(json!({"map": floor_map.id, "reason": reason})));
+            }
+        }
+    }
+    let mut missing = Vec::new();
+    for image_name in &classroom.images {
+        if !db_client.has_image(image_name.to_owned()).await? {
+            missing.push(image_name.to_owned());
+        }
+    }
+    if !missing.is_empty() {
+        return Err(ApiError::bad_request("classroom references unknown images")
+            .with_details(json!({"images": missing})));
+    }
+    Ok(())
+}
+
+/// Проверяет, что картинка названа и ее содержимое раскодируется из Base64
+fn validate_image(image: &CampusImage) -> Result<(), ApiError> {
+    if image.name.trim().is_empty() {
+        return Err(ApiError::bad_request("image name is empty"));
+    }
+    if let Err(e) = ImageResource::decode(image) {
+        return Err(ApiError::bad_request("image is not valid Base64")
+            .with_details(json!({"reason": e.to_string()})));
+    }
+    Ok(())
+}
+
+/// Отвечает сохраненной аудиторией, чтобы редактор видел итоговые данные
+async fn classroom_response(db_client: &dyn NavigationStore, classroom_name: String, status: StatusCode) -> Result<HttpResponse, ApiError> {
+    let classroom = db_client.get_classroom(classroom_name).await?;
+    Ok(HttpResponse::build(status).json(classroom))
+}
+
+#[cfg(test)]
+mod tests{
+    use serde_json::json;
+    use serial_test::serial;
+    use actix_service::Service;
+    use actix_web::http::StatusCode;
+    use actix_web::test::TestRequest;
+    use super::super::cache::CacheConfig;
+    use super::super::reload::DataFiles;
+    use super::super::server::{Server, StorageKind};
+
+    fn valid_classroom_data() -> String {
+        json!([{
+                "classroom": "УК3 104",
+                "description": "Крутая аудитория",
+                "images": ["UK3-left.png"]
+            },
+        ]).to_string()
+    }
+
+    fn valid_image_data() -> String {
+        json!([{
+                "name": "UK3-left.png",
+                "value": "iVBORw0KGgo=",
+            },
+            {
+                "name": "UK3-right.png",
+                "value": "iVBORw0KGgo=",
+            },
+            ]).to_string()
+    }
+
+    async fn test_app(admin_token: Option<&str>) -> impl actix_service::Service<actix_http::Request, Response = actix_web::dev::ServiceResponse, Error = actix_web::Error> {
+        let mut builder = Server::builder()
+            .classroom_data(valid_classroom_data())
+            .image_data(valid_image_data())
+            .storage(StorageKind::Memory);
+        if let Some(token) = admin_token {
+            builder = builder.admin_token(token.to_owned());
+        }
+        builder.build().test_start().await.unwrap()
+    }
+
+    fn authorized(request: TestRequest) -> TestRequest {
+        request.insert_header(("Authorization", "Bearer secret"))
+    }
+
+    #[actix_web::test]
+    #[serial]
+    async fn test_admin_auth(){
+        let app = test_app(None).await;
+        let req = authorized(TestRequest::delete().uri("/admin/images/UK3-right.png")).to_request();
+        assert_eq!(app.call(req).await.unwrap().status(), StatusCode::FORBIDDEN);
+
+        let app = test_app(Some("secret")).await;
+        let req = TestRequest::delete().uri("/admin/images/UK3-right.png").to_request();
+        assert_eq!(app.call(req).await.unwrap().status(), StatusCode::UNAUTHORIZED);
+        let req = TestRequest::delete()
+            .uri("/admin/images/UK3-right.png")
+            .insert_header(("Authorization", "Bearer secreT"))
+            .to_request();
+        assert_eq!(app.call(req).await.unwrap().status(), StatusCode::UNAUTHORIZED);
+        let req = authorized(TestRequest::delete().uri("/admin/images/UK3-right.png")).to_request();
+        assert_eq!(app.call(req).await.unwrap().status(), StatusCode::NO_CONTENT);
+
+        // Токен проверяется до разбора тела: без него схема не видна из ответа
+        let req = TestRequest::post().uri("/admin/classrooms").set_json(json!({"name": 1})).to_request();
+        let res = app.call(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        let res_body: serde_json::Value = actix_web::test::read_body_json(res).await;
+        assert_eq!(res_body["code"], json!("unauthorized"));
+        let req = authorized(TestRequest::post().uri("/admin/classrooms")).set_json(json!({"name": 1})).to_request();
+        assert_eq!(app.call(req).await.unwrap().status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    #[serial]
+    async fn test_admin_classrooms(){
+        let app = test_app(Some("secret")).await;
+        let classroom = json!({"classroom": "ГУК 305", "description": "Лекционная", "images": ["GUK-front.png"]});
+        let req = authorized(TestRequest::post().uri("/admin/classrooms")).set_json(&classroom).to_request();
+        assert_eq!(app.call(req).await.unwrap().status(), StatusCode::BAD_REQUEST);
+
+        let classroom = json!({"classroom": "ГУК 305", "description": "Лекционная", "images": ["UK3-right.png"]});
+        let req = authorized(TestRequest::post().uri("/admin/classrooms")).set_json(&classroom).to_request();
+        let res = app.call(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::CREATED);
+        let res_body: serde_json::Value = actix_web::test::read_body_json(res).await;
+        assert_eq!(res_body["location"]["floor"], json!(3));
+
+        let req = authorized(TestRequest::post().uri("/admin/classrooms")).set_json(&classroom).to_request();
+        assert_eq!(app.call(req).await.unwrap().status(), StatusCode::CONFLICT);
+
+        let uri = format!("/admin/classrooms/{}", urlencoding::encode("ГУК 305"));
+        let req = authorized(TestRequest::patch().uri(&uri)).set_json(json!({"description": "Поточная"})).to_request();
+        let res = app.call(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        let res_body: serde_json::Value = actix_web::test::read_body_json(res).await;
+        assert_eq!(res_body["description"], json!("Поточная"));
+        assert_eq!(res_body["images"], json!(["UK3-right.png"]));
+
+        let req = authorized(TestRequest::delete().uri("/admin/images/UK3-right.png")).to_request();
+        let res = app.call(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::CONFLICT);
+        let res_body: serde_json::Value = actix_web::test::read_body_json(res).await;
+        assert_eq!(res_body["code"], json!("conflict"));
+        assert_eq!(res_body["details"]["classrooms"], json!(["ГУК 305"]));
+
+        let renamed = json!({"classroom": "ГУК 306", "description": "Поточная", "images": []});
+        let req = authorized(TestRequest::put().uri(&uri)).set_json(&renamed).to_request();
+        assert_eq!(app.call(req).await.unwrap().status(), StatusCode::OK);
+
+        let req = authorized(TestRequest::delete().uri(&uri)).to_request();
+        assert_eq!(app.call(req).await.unwrap().status(), StatusCode::NOT_FOUND);
+        let uri = format!("/admin/classrooms/{}", urlencoding::encode("ГУК 306"));
+        let req = authorized(TestRequest::delete().uri(&uri)).to_request();
+        assert_eq!(app.call(req).await.unwrap().status(), StatusCode::NO_CONTENT);
+    }
+
+    #[actix_web::test]
+    #[serial]
+    async fn test_admin_images(){
+        let app = test_app(Some("secret")).await;
+        let image = json!({"name": "GUK-front.png", "value": "not base64!"});
+        let req = authorized(TestRequest::post().uri("/admin/images")).set_json(&image).to_request();
+        assert_eq!(app.call(req).await.unwrap().status(), StatusCode::BAD_REQUEST);
+
+        let image = json!({"name": "GUK-front.png", "value": "/9j/4AAQ"});
+        let req = authorized(TestRequest::post().uri("/admin/images")).set_json(&image).to_request();
+        assert_eq!(app.call(req).await.unwrap().status(), StatusCode::CREATED);
+
+        let req = authorized(TestRequest::put().uri("/admin/images/GUK-back.png")).set_json(&image).to_request();
+        assert_eq!(app.call(req).await.unwrap().status(), StatusCode::BAD_REQUEST);
+
+        let req = authorized(TestRequest::patch().uri("/admin/images/GUK-front.png"))
+            .set_json(json!({"value": "iVBORw0KGgo="}))
+            .to_request();
+        assert_eq!(app.call(req).await.unwrap().status(), StatusCode::OK);
+
+        let req = TestRequest::with_uri("/images/GUK-front.png").to_request();
+        let res = app.call(req).await.unwrap();
+        assert_eq!(res.headers().get("Content-Type").unwrap(), "image/png");
+    }
+
+    #[actix_web::test]
+    #[serial]
+    async fn test_admin_plan_bounds(){
+        use base64::Engine;
+        let mut plan = Vec::new();
+        image::RgbaImage::new(200, 100)
+            .write_to(&mut std::io::Cursor::new(&mut plan), image::ImageOutputFormat::Png)
+            .unwrap();
+        let image_data = json!([
+            {"name": "general_map.png", "value": "bWFw"},
+            {"name": "uk3-2.png", "value": base64::engine::general_purpose::STANDARD.encode(&plan)},
+        ]).to_string();
+        let maps = json!([
+            {"id": "campus", "title": "Кампус", "kind": "campus", "image": "general_map.png"},
+            {"id": "uk3-2", "title": "УК3, 2 этаж", "kind": "floor", "building": "УК3", "floor": 2, "image": "uk3-2.png"},
+        ]).to_string();
+        let app = Server::builder()
+            .classroom_data(valid_classroom_data())
+            .image_data(image_data)
+            .maps(maps)
+            .storage(StorageKind::Memory)
+            .admin_token("secret".to_owned())
+            .build()
+            .test_start()
+            .await
+            .unwrap();
+
+        let classroom = json!({"classroom": "УК3 204", "description": "", "images": [], "plan": {"points": [[300, 50]]}});
+        let req = authorized(TestRequest::post().uri("/admin/classrooms")).set_json(&classroom).to_request();
+        let res = app.call(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        let res_body: serde_json::Value = actix_web::test::read_body_json(res).await;
+        assert_eq!(res_body["details"]["map"], json!("uk3-2"));
+
+        let classroom = json!({"classroom": "УК3 204", "description": "", "images": [], "plan": {"points": [[150, 50]]}});
+        let req = authorized(TestRequest::post().uri("/admin/classrooms")).set_json(&classroom).to_request();
+        assert_eq!(app.call(req).await.unwrap().status(), StatusCode::CREATED);
+        let uri = format!("/admin/classrooms/{}", urlencoding::encode("УК3 204"));
+        let req = authorized(TestRequest::patch().uri(&uri)).set_json(json!({"plan": {"points": [[10, 10], [10, 100]]}})).to_request();
+        assert_eq!(app.call(req).await.unwrap().status(), StatusCode::BAD_REQUEST);
+
+        // Для этажа без плана разметку не с чем сверить
+        let classroom = json!({"classroom": "УК3 304", "description": "", "images": [], "plan": {"points": [[300, 50]]}});
+        let req = authorized(TestRequest::post().uri("/admin/classrooms")).set_json(&classroom).to_request();
+        assert_eq!(app.call(req).await.unwrap().status(), StatusCode::CREATED);
+    }
+
+    #[actix_web::test]
+    #[serial]
+    async fn test_admin_reload(){
+        let app = test_app(Some("secret")).await;
+        let req = authorized(TestRequest::post().uri("/admin/reload")).to_request();
+        assert_eq!(app.call(req).await.unwrap().status(), StatusCode::CONFLICT);
+
+        let dir = std::env::temp_dir().join(format!("nav_admin_reload_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let files = DataFiles{classrooms: dir.join("classrooms.json"), images: dir.join("images.json")};
+        std::fs::write(&files.classrooms, valid_classroom_data()).unwrap();
+        std::fs::write(&files.images, valid_image_data()).unwrap();
+        let app = Server::builder()
+            .classroom_data(valid_classroom_data())
+            .image_data(valid_image_data())
+            .storage(StorageKind::Memory)
+            .admin_token("secret".to_owned())
+            .data_files(files.clone())
+            .build()
+            .test_start()
+            .await
+            .unwrap();
+
+        std::fs::write(&files.classrooms, "not json").unwrap();
+        let req = authorized(TestRequest::post().uri("/admin/reload")).to_request();
+        assert_eq!(app.call(req).await.unwrap().status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        std::fs::write(&files.classrooms, json!([{"classroom": "ГУК 305", "description": "", "images": []}]).to_string()).unwrap();
+        let req = authorized(TestRequest::post().uri("/admin/reload")).to_request();
+        assert_eq!(app.call(req).await.unwrap().status(), StatusCode::OK);
+        let req = TestRequest::with_uri("/classroomlist").to_request();
+        let res_body: serde_json::Value = actix_web::test::read_body_json(app.call(req).await.unwrap()).await;
+        assert_eq!(res_body, json!(["ГУК 305"]));
+    }
+
+    #[actix_web::test]
+    #[serial]
+    async fn test_admin_cache(){
+        let app = test_app(Some("secret")).await;
+        let req = authorized(TestRequest::get().uri("/admin/cache")).to_request();
+        assert_eq!(app.call(req).await.unwrap().status(), StatusCode::CONFLICT);
+
+        let app = Server::builder()
+            .classroom_data(valid_classroom_data())
+            .image_data(valid_image_data())
+            .storage(StorageKind::Memory)
+            .admin_token("secret".to_owned())
+            .cache(CacheConfig::default())
+            .build()
+            .test_start()
+            .await
+            .unwrap();
+        for _ in 0..2 {
+            let req = TestRequest::with_uri("/classroomlist").to_request();
+            assert_eq!(app.call(req).await.unwrap().status(), StatusCode::OK);
+        }
+        let classroom = json!({"classroom": "ГУК 305", "description": "", "images": []});
+        let req = authorized(TestRequest::post().uri("/admin/classrooms")).set_json(&classroom).to_request();
+        assert_eq!(app.call(req).await.unwrap().status(), StatusCode::CREATED);
+        let req = TestRequest::with_uri("/classroomlist").to_request();
+        let res_body: serde_json::Value = actix_web::test::read_body_json(app.call(req).await.unwrap()).await;
+        assert_eq!(res_body, json!(["УК3 104", "ГУК 305"]));
+
+        let req = authorized(TestRequest::get().uri("/admin/cache")).to_request();
+        let res_body: serde_json::Value = actix_web::test::read_body_json(app.call(req).await.unwrap()).await;
+        assert_eq!(res_body["hits"], json!(1));
+        assert_eq!(res_body["invalidations"], json!(1));
+        let req = authorized(TestRequest::delete().uri("/admin/cache")).to_request();
+        assert_eq!(app.call(req).await.unwrap().status(), StatusCode::NO_CONTENT);
+    }
+}
@@ -0,0 +1,364 @@
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::{get, HttpRequest, HttpResponse, web};
+use std::future::Future;
+use utoipa::OpenApi;
+use utoipa_swagger_ui::SwaggerUi;
+use super::error::{ApiError, ErrorBody};
+use super::location::{ClassroomKind, ClassroomLocation};
+use super::maps::{CampusMap, MapCatalog, MapKind};
+use super::metrics::Metrics;
+use super::models::{ClassroomData, RoomPlan};
+use super::route::{CampusGraph, Route, RouteStep};
+use super::search::SearchCandidate;
+use super::server::{self, ImageMode, ImageRequest, MapResponse, RouteRequest, SearchRequest, SharedStore};
+use super::thumbnails::{VariantCache, VariantFormat};
+
+/// Префикс API первой версии
+pub const API_V1: &str = "/api/v1";
+/// Где лежит описание API в формате OpenAPI 3
+pub const OPENAPI_PATH: &str = "/api/v1/openapi.json";
+/// Где открывается Swagger UI
+pub const DOCS_PATH: &str = "/api/v1/docs/";
+
+/// Описание API первой версии, из него собирается /api/v1/openapi.json
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Сервис навигации бота БГТУ", description = "Аудитории, маршруты и карты кампуса БГТУ для чат-бота"),
+    paths(list_classrooms, get_classroom, get_classroom_plan, search_classrooms, get_route, list_maps, get_map, get_image),
+    components(schemas(
+        ClassroomList, ClassroomResponse, ClassroomLocation, ClassroomKind, RoomPlan, ImageMode,
+        SearchCandidate, Route, RouteStep, MapResponse, CampusMap, MapKind, VariantFormat, ErrorBody)),
+    tags(
+        (name = "classrooms", description = "Аудитории и планы этажей"),
+        (name = "navigation", description = "Поиск и маршруты"),
+        (name = "maps", description = "Карты и картинки кампуса"))
+)]
+pub struct ApiDoc;
+
+/// Список аудиторий
+#[derive(Debug, serde::Serialize, serde::Deserialize, utoipa::ToSchema)]
+pub struct ClassroomList{
+    pub classrooms: Vec<String>,
+}
+
+/// Аудитория в ответе /api/v1/classrooms/{name}
+#[derive(Debug, serde::Serialize, serde::Deserialize, utoipa::ToSchema)]
+pub struct ClassroomResponse{
+    pub classroom: String,
+    pub description: String,
+    /// Картинки в Base64 (images=inline) или ссылки вида /api/v1/images/{name} (images=url)
+    pub images: Vec<String>,
+    pub location: ClassroomLocation,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub plan: Option<RoomPlan>,
+}
+
+impl From<ClassroomData> for ClassroomResponse{
+    fn from(classroom: ClassroomData) -> Self {
+        Self{
+            classroom: classroom.classroom,
+            description: classroom.description,
+            images: classroom.images,
+            location: classroom.location,
+            plan: classroom.plan}
+    }
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+struct ClassroomQuery{
+    /// Как отдавать картинки, по умолчанию inline
+    #[serde(default)]
+    images: ImageMode,
+}
+
+/// Выдает описание API для /api/v1/openapi.json
+///
+/// # Примечание:
+/// utoipa берет лицензию из Cargo.toml, а там ее нет - пустая лицензия из описания убирается
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    let mut document = ApiDoc::openapi();
+    document.info.license = None;
+    document
+}
+
+/// Регистрирует API первой версии, его описание и Swagger UI
+///
+/// # Примечание:
+/// Swagger UI регистрируется до области /api/v1, иначе область ответит на его пути 404
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::redirect(DOCS_PATH.trim_end_matches('/'), DOCS_PATH))
+        .service(SwaggerUi::new(format!("{}{{_:.*}}", DOCS_PATH)).url(OPENAPI_PATH, openapi()))
+        .service(web::scope(API_V1)
+            .service(list_classrooms)
+            .service(get_classroom_plan)
+            .service(get_classroom)
+            .service(search_classrooms)
+            .service(get_route)
+            .service(list_maps)
+            .service(get_map)
+            .service(get_image));
+}
+
+/// Все аудитории по имени
+#[utoipa::path(context_path = "/api/v1", tag = "classrooms",
+    responses(
+        (status = 200, body = ClassroomList),
+        (status = 503, description = "База недоступна", body = ErrorBody)))]
+#[get("/classrooms")]
+async fn list_classrooms(db_client: SharedStore) -> Result<HttpResponse, ApiError> {
+    let classrooms = db_client.get_classroom_names().await?;
+    Ok(HttpResponse::Ok().json(ClassroomList{classrooms}))
+}
+
+/// Навигационные данные аудитории
+#[utoipa::path(context_path = "/api/v1", tag = "classrooms",
+    params(("name" = String, Path, description = "Имя аудитории, например \"УК3 104\""), ClassroomQuery),
+    responses(
+        (status = 200, body = ClassroomResponse),
+        (status = 404, description = "classroom_not_found с подсказками did_you_mean", body = ErrorBody)))]
+#[get("/classrooms/{name}")]
+async fn get_classroom(path: web::Path<String>, query: web::Query<ClassroomQuery>, db_client: SharedStore, metrics: web::Data<Metrics>) -> Result<HttpResponse, ApiError> {
+    let classroom = server::find_classroom(db_client.as_ref(), &metrics, &path, query.images, API_V1).await?;
+    Ok(HttpResponse::Ok().json(ClassroomResponse::from(classroom)))
+}
+
+/// План этажа с выделенной аудиторией
+#[utoipa::path(context_path = "/api/v1", tag = "classrooms",
+    params(("name" = String, Path, description = "Имя аудитории")),
+    responses(
+        (status = 200, description = "План этажа", content_type = "image/png"),
+        (status = 404, description = "classroom_not_found или plan_not_found", body = ErrorBody)))]
+#[get("/classrooms/{name}/plan")]
+async fn get_classroom_plan(path: web::Path<String>, db_client: SharedStore, maps: web::Data<MapCatalog>) -> Result<HttpResponse, ApiError> {
+    server::render_plan(db_client.as_ref(), &maps, &path).await
+}
+
+/// Поиск аудиторий по имени с опечатками
+#[utoipa::path(context_path = "/api/v1", tag = "navigation",
+    params(SearchRequest),
+    responses(
+        (status = 200, body = Vec<SearchCandidate>),
+        (status = 400, description = "Пустой запрос", body = ErrorBody)))]
+#[get("/search")]
+async fn search_classrooms(query: web::Query<SearchRequest>, db_client: SharedStore) -> Result<HttpResponse, ApiError> {
+    Ok(HttpResponse::Ok().json(server::find_candidates(db_client.as_ref(), &query).await?))
+}
+
+/// Маршрут между двумя аудиториями
+#[utoipa::path(context_path = "/api/v1", tag = "navigation",
+    params(RouteRequest),
+    responses(
+        (status = 200, body = Route),
+        (status = 404, description = "classroom_not_found или route_not_found", body = ErrorBody)))]
+#[get("/route")]
+async fn get_route(query: web::Query<RouteRequest>, db_client: SharedStore, campus_graph: web::Data<CampusGraph>) -> Result<HttpResponse, ApiError> {
+    Ok(HttpResponse::Ok().json(server::plan_route(db_client.as_ref(), &campus_graph, &query).await?))
+}
+
+/// Все карты кампуса без картинок
+#[utoipa::path(context_path = "/api/v1", tag = "maps",
+    responses((status = 200, body = Vec<MapResponse>)))]
+#[get("/maps")]
+async fn list_maps(maps: web::Data<MapCatalog>) -> HttpResponse {
+    let maps: Vec<MapResponse> = maps.maps().iter().map(|map|MapResponse::new(map, None, API_V1)).collect();
+    HttpResponse::Ok().json(maps)
+}
+
+/// Карта с картинкой в Base64
+#[utoipa::path(context_path = "/api/v1", tag = "maps",
+    params(("id" = String, Path, description = "id карты из /api/v1/maps, например \"campus\"")),
+    responses(
+        (status = 200, body = MapResponse),
+        (status = 404, description = "map_not_found со списком карт", body = ErrorBody)))]
+#[get("/maps/{id}")]
+async fn get_map(path: web::Path<String>, db_client: SharedStore, maps: web::Data<MapCatalog>) -> Result<HttpResponse, ApiError> {
+    Ok(HttpResponse::Ok().json(server::find_map(db_client.as_ref(), &maps, &path, API_V1).await?))
+}
+
+/// Картинка кампуса, по параметрам уменьшенная и перекодированная
+#[utoipa::path(context_path = "/api/v1", tag = "maps",
+    params(("name" = String, Path, description = "Название картинки"), ImageRequest),
+    responses(
+        (status = 200, description = "Картинка с ETag и Cache-Control", content(
+            ([u8] = "image/png"), ([u8] = "image/jpeg"), ([u8] = "image/webp"))),
+        (status = 304, description = "Картинка с таким ETag уже есть у клиента"),
+        (status = 404, description = "image_not_found", body = ErrorBody)))]
+#[get("/images/{name}")]
+async fn get_image(request: HttpRequest, path: web::Path<String>, query: web::Query<ImageRequest>, db_client: SharedStore, variants: web::Data<VariantCache>) -> Result<HttpResponse, ApiError> {
+    server::serve_image(&request, path.into_inner(), query.into_inner(), db_client.as_ref(), &variants).await
+}
+
+/// Путь API v1, который заменяет старый путь из запроса
+///
+/// # Примечание:
+/// Выдает None для путей, у которых нет старой версии (/healthz, /admin, сам /api/v1)
+fn successor(request: &HttpRequest) -> Option<String> {
+    let name = ||url::form_urlencoded::parse(request.query_string().as_bytes())
+        .find(|(key, _)|key == "name")
+        .map(|(_, value)|urlencoding::encode(&value).into_owned())
+        .unwrap_or_default();
+    let same_path = ||match request.query_string() {
+        "" => format!("{}{}", API_V1, request.path()),
+        query => format!("{}{}?{}", API_V1, request.path(), query),
+    };
+    match request.match_pattern()?.as_str() {
+        "/classroomlist" => Some(format!("{}/classrooms", API_V1)),
+        "/classroom" => Some(format!("{}/classrooms/{}", API_V1, name())),
+        "/classroom/plan" => Some(format!("{}/classrooms/{}/plan", API_V1, name())),
+        "/map" => {
+            let maps = request.app_data::<web::Data<MapCatalog>>()?;
+            Some(format!("{}/maps/{}", API_V1, urlencoding::encode(&maps.general().id)))
+        },
+        "/images/{name}" | "/search" | "/route" | "/maps" | "/maps/{id}" => Some(same_path()),
+        _ => None,
+    }
+}
+
+/// Middleware для App::wrap_fn: помечает ответы старых путей как устаревшие
+///
+/// # Примечание:
+/// Добавляет "Deprecation: true" и Link на замену в /api/v1 (RFC 8594), сами ответы не меняются,
+/// чтобы старые версии бота продолжали работать
+pub fn mark_deprecated<S, B>(request: ServiceRequest, service: &S) -> impl Future<Output = Result<ServiceResponse<B>, actix_web::Error>>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error>,
+{
+    let response = service.call(request);
+    async move {
+        let mut response = response.await?;
+        if let Some(successor) = successor(response.request()) {
+            let link = HeaderValue::from_str(&format!("<{}>; rel=\"successor-version\"", successor));
+            let headers = response.headers_mut();
+            headers.insert(HeaderName::from_static("deprecation"), HeaderValue::from_static("true"));
+            if let Ok(link) = link {
+                headers.insert(HeaderName::from_static("link"), link);
+            }
+        }
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use serde_json::json;
+    use serial_test::serial;
+    use actix_service::Service;
+    use actix_web::http::StatusCode;
+    use actix_web::test::TestRequest;
+    use super::*;
+    use super::super::server::{Server, StorageKind};
+
+    fn valid_classroom_data() -> String {
+        json!([
+            {"classroom": "УК3 104", "description": "Крутая аудитория", "images": ["uk3-front.png"]},
+            {"classroom": "УК3 105", "description": "", "images": []}
+        ]).to_string()
+    }
+
+    fn valid_image_data() -> String {
+        json!([{"name": "uk3-front.png", "value": "iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAQAAAC1HAwCAAAAC0lEQVR42mNkYAAAAAYAAjCB0C8AAAAASUVORK5CYII="}]).to_string()
+    }
+
+    async fn test_app() -> impl Service<actix_http::Request, Response = ServiceResponse, Error = actix_web::Error> {
+        Server::builder()
+            .classroom_data(valid_classroom_data())
+            .image_data(valid_image_data())
+            .storage(StorageKind::Memory)
+            .build()
+            .test_start()
+            .await
+            .unwrap()
+    }
+
+    #[actix_web::test]
+    #[serial]
+    async fn test_v1_classrooms(){
+        let app = test_app().await;
+
+        let res = app.call(TestRequest::with_uri("/api/v1/classrooms").to_request()).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert!(!res.headers().contains_key("deprecation"));
+        let res_body: ClassroomList = actix_web::test::read_body_json(res).await;
+        assert_eq!(res_body.classrooms, vec!["УК3 104".to_owned(), "УК3 105".to_owned()]);
+
+        let uri = format!("/api/v1/classrooms/{}?images=url", urlencoding::encode("УК3 104"));
+        let res = app.call(TestRequest::with_uri(&uri).to_request()).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        let res_body: ClassroomResponse = actix_web::test::read_body_json(res).await;
+        assert_eq!(res_body.images, vec!["/api/v1/images/uk3-front.png".to_owned()]);
+        assert_eq!(res_body.location.building.as_deref(), Some("УК3"));
+
+        let res = app.call(TestRequest::with_uri("/api/v1/images/uk3-front.png").to_request()).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers().get("content-type").unwrap(), "image/png");
+
+        let uri = format!("/api/v1/classrooms/{}", urlencoding::encode("УК3 106"));
+        let res = app.call(TestRequest::with_uri(&uri).to_request()).await.unwrap();
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        let res_body: serde_json::Value = actix_web::test::read_body_json(res).await;
+        assert_eq!(res_body["code"], "classroom_not_found");
+        assert!(!res_body["details"]["did_you_mean"].as_array().unwrap().is_empty());
+
+        let res = app.call(TestRequest::with_uri("/api/v1/maps").to_request()).await.unwrap();
+        let res_body: serde_json::Value = actix_web::test::read_body_json(res).await;
+        assert!(res_body[0]["image_url"].as_str().unwrap().starts_with("/api/v1/images/"));
+    }
+
+    #[actix_web::test]
+    #[serial]
+    async fn test_legacy_routes_deprecated(){
+        let app = test_app().await;
+
+        let res = app.call(TestRequest::with_uri("/classroomlist").to_request()).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers().get("deprecation").unwrap(), "true");
+        assert_eq!(res.headers().get("link").unwrap(), "</api/v1/classrooms>; rel=\"successor-version\"");
+
+        let uri = format!("/classroom?name={}", urlencoding::encode("УК3 104"));
+        let res = app.call(TestRequest::with_uri(&uri).to_request()).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        let link = res.headers().get("link").unwrap().to_str().unwrap().to_owned();
+        assert_eq!(link, format!("</api/v1/classrooms/{}>; rel=\"successor-version\"", urlencoding::encode("УК3 104")));
+        let res_body: serde_json::Value = actix_web::test::read_body_json(res).await;
+        assert_eq!(res_body["images"].as_array().unwrap().len(), 1);
+
+        let res = app.call(TestRequest::with_uri("/search?q=104").to_request()).await.unwrap();
+        assert_eq!(res.headers().get("link").unwrap(), "</api/v1/search?q=104>; rel=\"successor-version\"");
+
+        let res = app.call(TestRequest::with_uri("/healthz").to_request()).await.unwrap();
+        assert!(!res.headers().contains_key("deprecation"));
+    }
+
+    #[actix_web::test]
+    #[serial]
+    async fn test_openapi(){
+        let app = test_app().await;
+
+        let res = app.call(TestRequest::with_uri(OPENAPI_PATH).to_request()).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        let res_body: serde_json::Value = actix_web::test::read_body_json(res).await;
+        assert!(res_body["openapi"].as_str().unwrap().starts_with("3."));
+        let paths = res_body["paths"].as_object().unwrap();
+        for path in ["/api/v1/classrooms", "/api/v1/classrooms/{name}", "/api/v1/classrooms/{name}/plan",
+                     "/api/v1/search", "/api/v1/route", "/api/v1/maps", "/api/v1/maps/{id}", "/api/v1/images/{name}"] {
+            assert!(paths.contains_key(path), "{} is not documented", path);
+        }
+
+        let document = res_body.to_string();
+        let schemas = res_body["components"]["schemas"].as_object().unwrap();
+        for reference in document.split("\"#/components/schemas/").skip(1) {
+            let name = reference.split('"').next().unwrap();
+            assert!(schemas.contains_key(name), "{} is referenced but not defined", name);
+        }
+
+        assert!(res_body["info"].get("license").is_none());
+
+        let res = app.call(TestRequest::with_uri("/api/v1/docs").to_request()).await.unwrap();
+        assert!(res.status().is_redirection());
+        let res = app.call(TestRequest::with_uri(DOCS_PATH).to_request()).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert!(res.headers().get("content-type").unwrap().to_str().unwrap().starts_with("text/html"));
+    }
+}
@@ -0,0 +1,256 @@
+use async_trait::async_trait;
+use mongodb::bson::{doc, spec::BinarySubtype, Binary, Bson};
+use mongodb::gridfs::GridFsBucket;
+use mongodb::options::ReplaceOptions;
+use mongodb::{Collection, Database};
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+use super::error::StoreError;
+
+/// Где DBClient хранит содержимое картинок
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum ImageStorage{
+    /// Документы {_id: хэш, data: двоичные данные} в коллекции image_blobs.
+    /// Одна картинка не может быть больше 16 МБ - ограничения документа Монго
+    #[default]
+    Inline,
+    /// Файлы в папке на диске, имя файла - хэш содержимого
+    Disk(PathBuf),
+    /// GridFS-бакет image_blobs: картинки хранятся кусками и могут быть любого размера
+    GridFs,
+}
+
+impl FromStr for ImageStorage{
+    type Err = String;
+
+    /// Разбирает "inline", "gridfs" или "disk:/путь/к/папке"
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().split_once(':') {
+            Some(("disk", path)) if !path.is_empty() => Ok(Self::Disk(PathBuf::from(path))),
+            None if value.trim() == "inline" => Ok(Self::Inline),
+            None if value.trim() == "gridfs" => Ok(Self::GridFs),
+            _ => Err(format!("Unknown image storage: {}, expected inline/gridfs/disk:<path>", value)),
+        }
+    }
+}
+
+/// Хранилище содержимого картинок по SHA-256 (см. images::content_hash)
+///
+/// # Примечание:
+/// Запись идемпотентна: одинаковое содержимое под одним хэшем хранится один раз.
+/// За тем, на какие хэши еще ссылаются названия картинок, следит DBClient
+#[async_trait]
+pub trait BlobStore: Send + Sync + fmt::Debug {
+    /// Сохраняет содержимое, если его еще нет
+    async fn put(&self, hash: &str, data: &[u8]) -> Result<(), StoreError>;
+
+    /// Выдает содержимое, StoreError::ImageNotFound если его нет
+    async fn get(&self, hash: &str) -> Result<Vec<u8>, StoreError>;
+
+    /// Удаляет содержимое, отсутствие не считается ошибкой
+    async fn delete(&self, hash: &str) -> Result<(), StoreError>;
+}
+
+/// Создает хранилище содержимого картинок выбранного типа
+///
+/// # Аргументы:
+/// - storage: тип хранилища
+/// - database: база, в которой лежат коллекция или бакет для Inline и GridFs
+pub fn open_blob_store(storage: &ImageStorage, database: &Database) -> Box<dyn BlobStore> {
+    const BLOBS: &str = "image_blobs";
+    match storage {
+        ImageStorage::Inline => Box::new(InlineBlobs{collection: database.collection(BLOBS)}),
+        ImageStorage::Disk(root) => Box::new(DiskBlobs{root: root.to_owned()}),
+        ImageStorage::GridFs => {
+            let options = mongodb::options::GridFsBucketOptions::builder().bucket_name(BLOBS.to_owned()).build();
+            Box::new(GridFsBlobs{bucket: database.gridfs_bucket(options)})
+        },
+    }
+}
+
+/// Ошибка повторяющегося ключа: то же содержимое в это же время записала другая реплика
+fn is_duplicate_key(e: &mongodb::error::Error) -> bool {
+    use mongodb::error::{ErrorKind, WriteFailure};
+    const DUPLICATE_KEY: i32 = 11000;
+    match e.kind.as_ref() {
+        ErrorKind::Write(WriteFailure::WriteError(error)) => error.code == DUPLICATE_KEY,
+        ErrorKind::Command(error) => error.code == DUPLICATE_KEY,
+        _ => false,
+    }
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct BlobDocument{
+    #[serde(rename = "_id")]
+    hash: String,
+    data: Binary,
+}
+
+/// Содержимое картинок в обычной коллекции, двоичными данными вместо строки Base64
+#[derive(Debug)]
+struct InlineBlobs{
+    collection: Collection<BlobDocument>,
+}
+
+#[async_trait]
+impl BlobStore for InlineBlobs{
+    async fn put(&self, hash: &str, data: &[u8]) -> Result<(), StoreError> {
+        let blob = BlobDocument{
+            hash: hash.to_owned(),
+            data: Binary{subtype: BinarySubtype::Generic, bytes: data.to_vec()}};
+        let result = self.collection
+            .replace_one(doc!{"_id": hash}, blob, ReplaceOptions::builder().upsert(true).build())
+            .await;
+        match result {
+            Err(e) if !is_duplicate_key(&e) => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    async fn get(&self, hash: &str) -> Result<Vec<u8>, StoreError> {
+        let blob = self.collection.find_one(doc!{"_id": hash}, None).await?;
+        blob.map(|x|x.data.bytes).ok_or(StoreError::ImageNotFound(hash.to_owned()))
+    }
+
+    async fn delete(&self, hash: &str) -> Result<(), StoreError> {
+        self.collection.delete_one(doc!{"_id": hash}, None).await?;
+        Ok(())
+    }
+}
+
+/// Содержимое картинок в GridFS, id файла - хэш
+#[derive(Debug)]
+struct GridFsBlobs{
+    bucket: GridFsBucket,
+}
+
+impl GridFsBlobs{
+    /// Есть ли в бакете файл с таким id
+    async fn contains(&self, hash: &str) -> Result<bool, StoreError> {
+        let mut files = self.bucket.find(doc!{"_id": hash}, None).await?;
+        Ok(files.advance().await?)
+    }
+}
+
+#[async_trait]
+impl BlobStore for GridFsBlobs{
+    /// # Примечание:
+    /// Если файл с этим хэшем в это же время загружает другая реплика, вставка кусков
+    /// или описания файла упирается в уникальный индекс. Это не ошибка: содержимое то же самое
+    async fn put(&self, hash: &str, data: &[u8]) -> Result<(), StoreError> {
+        if self.contains(hash).await? {
+            return Ok(());
+        }
+        let result = self.bucket
+            .upload_from_futures_0_3_reader_with_id(Bson::String(hash.to_owned()), hash, data, None)
+            .await;
+        match result {
+            Err(e) if !is_duplicate_key(&e) => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    async fn get(&self, hash: &str) -> Result<Vec<u8>, StoreError> {
+        if !self.contains(hash).await? {
+            return Err(StoreError::ImageNotFound(hash.to_owned()));
+        }
+        let mut data = Vec::new();
+        self.bucket.download_to_futures_0_3_writer(Bson::String(hash.to_owned()), &mut data).await?;
+        Ok(data)
+    }
+
+    async fn delete(&self, hash: &str) -> Result<(), StoreError> {
+        if self.contains(hash).await? {
+            self.bucket.delete(Bson::String(hash.to_owned())).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Содержимое картинок в файлах root/ab/abcdef..., где abcdef... - хэш
+///
+/// # Примечание:
+/// Папка должна быть общей для всех реплик сервиса, например volume в docker-compose
+#[derive(Debug)]
+pub struct DiskBlobs{
+    root: PathBuf,
+}
+
+impl DiskBlobs{
+    pub fn new(root: PathBuf) -> Self {
+        Self{root}
+    }
+
+    fn path(&self, hash: &str) -> Result<PathBuf, StoreError> {
+        if hash.len() < 3 || !hash.bytes().all(|x|x.is_ascii_hexdigit()) {
+            return Err(StoreError::Internal(format!("bad content hash: {}", hash)));
+        }
+        Ok(self.root.join(&hash[..2]).join(hash))
+    }
+}
+
+#[async_trait]
+impl BlobStore for DiskBlobs{
+    /// # Примечание:
+    /// Пишет во временный файл и переименовывает его, чтобы читатели не увидели недописанную картинку
+    async fn put(&self, hash: &str, data: &[u8]) -> Result<(), StoreError> {
+        let path = self.path(hash)?;
+        if tokio::fs::try_exists(&path).await? {
+            return Ok(());
+        }
+        let dir = path.parent().expect("blob path has a parent");
+        tokio::fs::create_dir_all(dir).await?;
+        let temp = dir.join(format!("{}.{}.tmp", hash, std::process::id()));
+        tokio::fs::write(&temp, data).await?;
+        tokio::fs::rename(&temp, &path).await?;
+        Ok(())
+    }
+
+    async fn get(&self, hash: &str) -> Result<Vec<u8>, StoreError> {
+        match tokio::fs::read(self.path(hash)?).await {
+            Ok(data) => Ok(data),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Err(StoreError::ImageNotFound(hash.to_owned())),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn delete(&self, hash: &str) -> Result<(), StoreError> {
+        match tokio::fs::remove_file(self.path(hash)?).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use super::super::images::content_hash;
+
+    #[test]
+    fn test_image_storage_parse(){
+        assert_eq!("inline".parse::<ImageStorage>(), Ok(ImageStorage::Inline));
+        assert_eq!(" gridfs ".parse::<ImageStorage>(), Ok(ImageStorage::GridFs));
+        assert_eq!("disk:/data/images".parse::<ImageStorage>(), Ok(ImageStorage::Disk("/data/images".into())));
+        assert!("disk:".parse::<ImageStorage>().is_err());
+        assert!("s3".parse::<ImageStorage>().is_err());
+    }
+
+    #[actix_web::test]
+    async fn test_disk_blobs(){
+        let root = std::env::temp_dir().join(format!("navigation-blobs-{}", std::process::id()));
+        let blobs = DiskBlobs::new(root.clone());
+        let hash = content_hash(b"picture");
+        blobs.put(&hash, b"picture").await.unwrap();
+        blobs.put(&hash, b"picture").await.unwrap();
+        assert_eq!(blobs.get(&hash).await.unwrap(), b"picture");
+        assert!(root.join(&hash[..2]).join(&hash).exists());
+
+        blobs.delete(&hash).await.unwrap();
+        blobs.delete(&hash).await.unwrap();
+        assert_eq!(blobs.get(&hash).await, Err(StoreError::ImageNotFound(hash)));
+        assert!(blobs.get("../../etc/passwd").await.is_err());
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
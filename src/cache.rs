@@ -0,0 +1,479 @@
+use async_trait::async_trait;
+use std::any::Any;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use super::error::StoreError;
+use super::models::{CampusImage, ClassroomData};
+use super::store::{Dataset, NavigationStore, StoreStatus};
+
+/// Ограничения кэша ответов
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheConfig{
+    /// Сколько ответов держать одновременно, при переполнении вытесняются самые старые
+    pub max_entries: usize,
+    /// Сколько байт ответов держать одновременно, ответы больше этого не кэшируются
+    pub max_bytes: usize,
+    /// Сколько живет ответ, даже если данные не менялись
+    pub ttl: Duration,
+}
+
+impl Default for CacheConfig{
+    fn default() -> Self {
+        Self{max_entries: 1024, max_bytes: 64 * 1024 * 1024, ttl: Duration::from_secs(300)}
+    }
+}
+
+/// Счетчики кэша
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CacheStats{
+    pub hits: u64,
+    pub misses: u64,
+    /// Сколько раз кэш сбрасывался из-за изменения данных
+    pub invalidations: u64,
+    pub entries: usize,
+    /// Примерный размер закэшированных ответов
+    pub bytes: usize,
+}
+
+/// По какому запросу закэширован ответ
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum CacheKey{
+    ClassroomList,
+    ClassroomNames,
+    Classroom(String),
+    Map(String),
+    /// Одна картинка: общая для /images и аудиторий с картинками в Base64
+    Image(String),
+}
+
+/// Примерный размер ответа в байтах, для CacheConfig::max_bytes
+trait CacheWeight{
+    fn weight(&self) -> usize;
+}
+
+impl CacheWeight for String{
+    fn weight(&self) -> usize {
+        self.len()
+    }
+}
+
+impl CacheWeight for Vec<String>{
+    fn weight(&self) -> usize {
+        self.iter().map(|x|x.len() + std::mem::size_of::<String>()).sum()
+    }
+}
+
+impl CacheWeight for CampusImage{
+    fn weight(&self) -> usize {
+        std::mem::size_of::<Self>() + self.name.len() + self.value.len()
+    }
+}
+
+impl CacheWeight for ClassroomData{
+    fn weight(&self) -> usize {
+        std::mem::size_of::<Self>() + self.classroom.len() + self.description.len() + self.images.weight()
+    }
+}
+
+struct CacheEntry{
+    value: Arc<dyn Any + Send + Sync>,
+    inserted: Instant,
+    bytes: usize,
+}
+
+#[derive(Default)]
+struct CacheState{
+    entries: HashMap<CacheKey, CacheEntry>,
+    /// Сумма CacheEntry::bytes
+    bytes: usize,
+    /// Растет при каждом сбросе, чтобы не сохранить ответ, загруженный до изменения данных
+    generation: u64,
+}
+
+impl CacheState{
+    fn remove(&mut self, key: &CacheKey) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.bytes -= entry.bytes;
+        }
+    }
+
+    /// Освобождает место: выкидывает просроченные ответы, а если таких нет - самый старый
+    fn evict(&mut self, ttl: Duration) {
+        let expired: Vec<CacheKey> = self.entries.iter()
+            .filter(|(_, entry)|entry.inserted.elapsed() >= ttl)
+            .map(|(key, _)|key.clone())
+            .collect();
+        if !expired.is_empty() {
+            for key in &expired {
+                self.remove(key);
+            }
+            return;
+        }
+        let oldest = self.entries.iter()
+            .min_by_key(|(_, entry)|entry.inserted)
+            .map(|(key, _)|key.clone());
+        if let Some(key) = oldest {
+            self.remove(&key);
+        }
+    }
+}
+
+/// Кэш ответов хранилища
+///
+/// # Примечание:
+/// Кэшируются только успешные ответы. Отдельно от CachedStore, чтобы
+/// админский API мог читать счетчики и сбрасывать кэш
+pub struct ResponseCache{
+    config: CacheConfig,
+    state: Mutex<CacheState>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    invalidations: AtomicU64,
+}
+
+impl ResponseCache{
+    pub fn new(config: CacheConfig) -> Self {
+        Self{
+            config,
+            state: Mutex::new(CacheState::default()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            invalidations: AtomicU64::new(0)}
+    }
+
+    /// Выдает счетчики кэша
+    pub fn stats(&self) -> CacheStats {
+        let state = self.state.lock().unwrap();
+        CacheStats{
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            invalidations: self.invalidations.load(Ordering::Relaxed),
+            entries: state.entries.len(),
+            bytes: state.bytes}
+    }
+
+    /// Сбрасывает все закэшированные ответы
+    pub fn invalidate(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.entries.clear();
+        state.bytes = 0;
+        state.generation += 1;
+        self.invalidations.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Выдает ответ из кэша или загружает его и запоминает
+    async fn get_or_load<T, F>(&self, key: CacheKey, load: F) -> Result<T, StoreError>
+    where
+        T: CacheWeight + Clone + Send + Sync + 'static,
+        F: Future<Output = Result<T, StoreError>>,
+    {
+        let generation = match self.lookup(&key) {
+            Ok(value) => return Ok(value),
+            Err(generation) => generation,
+        };
+        let value = load.await?;
+        self.store(key, &value, generation);
+        Ok(value)
+    }
+
+    /// Выдает картинки из кэша, а ненайденные в нем загружает одним запросом
+    ///
+    /// # Примечание:
+    /// Картинки кэшируются по одной, поэтому аудитории с общими картинками
+    /// и /images/{name} используют одни и те же записи
+    async fn get_or_load_images<F, Fut>(&self, image_names: &[String], load: F) -> Result<Vec<CampusImage>, StoreError>
+    where
+        F: FnOnce(Vec<String>) -> Fut,
+        Fut: Future<Output = Result<Vec<CampusImage>, StoreError>>,
+    {
+        let mut found: HashMap<String, CampusImage> = HashMap::new();
+        let mut missing = Vec::new();
+        let mut generation = None;
+        for name in image_names {
+            if found.contains_key(name) || missing.contains(name) {
+                continue;
+            }
+            match self.lookup::<CampusImage>(&CacheKey::Image(name.to_owned())) {
+                Ok(image) => {
+                    found.insert(name.to_owned(), image);
+                },
+                Err(current) => {
+                    generation.get_or_insert(current);
+                    missing.push(name.to_owned());
+                },
+            }
+        }
+        if let Some(generation) = generation {
+            match load(missing).await {
+                Ok(images) => for image in images {
+                    self.store(CacheKey::Image(image.name.to_owned()), &image, generation);
+                    found.insert(image.name.to_owned(), image);
+                },
+                Err(StoreError::ImageNotFound(_)) => {},
+                Err(e) => return Err(e),
+            }
+        }
+        let images: Vec<CampusImage> = image_names.iter()
+            .filter_map(|name|found.get(name).cloned())
+            .collect();
+        if images.is_empty() {
+            return Err(StoreError::ImageNotFound(image_names.join(", ")));
+        }
+        Ok(images)
+    }
+
+    /// Выдает ответ из кэша, а если его нет - поколение кэша для store
+    fn lookup<T>(&self, key: &CacheKey) -> Result<T, u64>
+    where
+        T: Clone + 'static,
+    {
+        let mut state = self.state.lock().unwrap();
+        let cached = state.entries.get(key)
+            .filter(|entry|entry.inserted.elapsed() < self.config.ttl)
+            .and_then(|entry|entry.value.downcast_ref::<T>().cloned());
+        if let Some(value) = cached {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(value);
+        }
+        state.remove(key);
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        Err(state.generation)
+    }
+
+    /// Запоминает ответ, если кэш не сбрасывался с начала загрузки
+    ///
+    /// # Аргументы:
+    /// - generation: поколение кэша, которое выдал lookup до загрузки
+    fn store<T>(&self, key: CacheKey, value: &T, generation: u64)
+    where
+        T: CacheWeight + Clone + Send + Sync + 'static,
+    {
+        let bytes = value.weight();
+        let mut state = self.state.lock().unwrap();
+        if state.generation == generation && self.config.max_entries > 0 && bytes <= self.config.max_bytes {
+            state.remove(&key);
+            while !state.entries.is_empty()
+                && (state.entries.len() >= self.config.max_entries || state.bytes + bytes > self.config.max_bytes) {
+                state.evict(self.config.ttl);
+            }
+            state.bytes += bytes;
+            state.entries.insert(key, CacheEntry{value: Arc::new(value.clone()), inserted: Instant::now(), bytes});
+        }
+    }
+}
+
+/// Хранилище с кэшем ответов перед другим хранилищем
+///
+/// # Что делает?
+/// - Кэширует список аудиторий, данные аудиторий, картинки и карты для /map
+/// - Сбрасывает кэш после любых правок и перезагрузки данных
+///
+/// # Примечание:
+/// Правки, сделанные другой репликой сервиса, видны после истечения ttl.
+/// Картинки кэшируются по одной под тем же ограничением max_bytes, а аудитория
+/// с картинками в Base64 собирается из закэшированной аудитории и картинок.
+/// get_classroom_data обработчики не используют, он не кэшируется
+pub struct CachedStore{
+    inner: Arc<dyn NavigationStore>,
+    cache: Arc<ResponseCache>,
+}
+
+impl CachedStore{
+    pub fn new(inner: Arc<dyn NavigationStore>, cache: Arc<ResponseCache>) -> Self {
+        Self{inner, cache}
+    }
+
+    /// Выполняет правку и сбрасывает кэш, если она удалась
+    async fn invalidating<F>(&self, edit: F) -> Result<(), StoreError>
+    where
+        F: Future<Output = Result<(), StoreError>>,
+    {
+        edit.await?;
+        self.cache.invalidate();
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl NavigationStore for CachedStore{
+    async fn get_classroom_list(&self) -> Result<String, StoreError> {
+        self.cache.get_or_load(CacheKey::ClassroomList, self.inner.get_classroom_list()).await
+    }
+
+    async fn get_classroom_names(&self) -> Result<Vec<String>, StoreError> {
+        self.cache.get_or_load(CacheKey::ClassroomNames, self.inner.get_classroom_names()).await
+    }
+
+    async fn get_classroom(&self, classroom_name: String) -> Result<ClassroomData, StoreError> {
+        let load = self.inner.get_classroom(classroom_name.to_owned());
+        self.cache.get_or_load(CacheKey::Classroom(classroom_name), load).await
+    }
+
+    async fn get_classroom_data(&self, classroom_name: String) -> Result<String, StoreError> {
+        self.inner.get_classroom_data(classroom_name).await
+    }
+
+    async fn get_campus_image(&self, image_name: String) -> Result<CampusImage, StoreError> {
+        let load = self.inner.get_campus_image(image_name.to_owned());
+        self.cache.get_or_load(CacheKey::Image(image_name), load).await
+    }
+
+    async fn get_campus_images(&self, image_names: &[String]) -> Result<Vec<CampusImage>, StoreError> {
+        self.cache.get_or_load_images(image_names, |missing|async move {
+            self.inner.get_campus_images(&missing).await
+        }).await
+    }
+
+    async fn has_image(&self, image_name: String) -> Result<bool, StoreError> {
+        self.inner.has_image(image_name).await
+    }
+
+    async fn get_map(&self, image_name: String) -> Result<String, StoreError> {
+        let load = self.inner.get_map(image_name.to_owned());
+        self.cache.get_or_load(CacheKey::Map(image_name), load).await
+    }
+
+    async fn get_classrooms_with_image(&self, image_name: String) -> Result<Vec<String>, StoreError> {
+        self.inner.get_classrooms_with_image(image_name).await
+    }
+
+    async fn insert_classroom(&self, classroom: ClassroomData) -> Result<(), StoreError> {
+        self.invalidating(self.inner.insert_classroom(classroom)).await
+    }
+
+    async fn replace_classroom(&self, classroom_name: String, classroom: ClassroomData) -> Result<(), StoreError> {
+        self.invalidating(self.inner.replace_classroom(classroom_name, classroom)).await
+    }
+
+    async fn delete_classroom(&self, classroom_name: String) -> Result<(), StoreError> {
+        self.invalidating(self.inner.delete_classroom(classroom_name)).await
+    }
+
+    async fn insert_image(&self, image: CampusImage) -> Result<(), StoreError> {
+        self.invalidating(self.inner.insert_image(image)).await
+    }
+
+    async fn replace_image(&self, image: CampusImage) -> Result<(), StoreError> {
+        self.invalidating(self.inner.replace_image(image)).await
+    }
+
+    async fn delete_image(&self, image_name: String) -> Result<(), StoreError> {
+        self.invalidating(self.inner.delete_image(image_name)).await
+    }
+
+    async fn reload(&self, classroom_data: String, image_data: String) -> Result<(), StoreError> {
+        self.invalidating(self.inner.reload(classroom_data, image_data)).await
+    }
+
+    async fn export(&self) -> Result<Dataset, StoreError> {
+        self.inner.export().await
+    }
+
+    async fn status(&self) -> Result<StoreStatus, StoreError> {
+        self.inner.status().await
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use serde_json::json;
+    use super::*;
+    use super::super::memory_store::MemoryStore;
+
+    fn cached_store(config: CacheConfig) -> (CachedStore, Arc<ResponseCache>) {
+        let classroom_data = json!([
+            {"classroom": "УК3 104", "description": "Крутая аудитория", "images": ["UK3-left.png"]},
+            {"classroom": "УК3 205", "description": "Менее крутая аудитория", "images": []},
+        ]).to_string();
+        let image_data = json!([{"name": "UK3-left.png", "value": "bibabob0"}]).to_string();
+        let inner = Arc::new(MemoryStore::new(classroom_data, image_data).unwrap());
+        let cache = Arc::new(ResponseCache::new(config));
+        (CachedStore::new(inner, cache.clone()), cache)
+    }
+
+    #[actix_web::test]
+    async fn test_cache_hits(){
+        let (store, cache) = cached_store(CacheConfig::default());
+        let first = store.get_classroom("УК3 104".to_owned()).await.unwrap();
+        let second = store.get_classroom("УК3 104".to_owned()).await.unwrap();
+        assert_eq!(first.description, second.description);
+        store.get_classroom_list().await.unwrap();
+        assert!(store.get_classroom("УК4 104".to_owned()).await.is_err());
+        assert!(store.get_classroom("УК4 104".to_owned()).await.is_err());
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses, stats.invalidations, stats.entries), (1, 4, 0, 2));
+        assert_eq!(stats.bytes, first.weight() + store.get_classroom_list().await.unwrap().len());
+
+        // Кэш не меняет результат: имя ищется в хранилище как есть
+        assert!(store.get_classroom("  УК3   104 ".to_owned()).await.is_err());
+        store.get_classroom_data("УК3 104".to_owned()).await.unwrap();
+        assert_eq!(cache.stats().entries, 2);
+    }
+
+    #[actix_web::test]
+    async fn test_cache_images(){
+        let (store, cache) = cached_store(CacheConfig::default());
+        let names = ["UK3-left.png", "UK3-back.png"].map(String::from);
+        let images = store.get_campus_images(&names).await.unwrap();
+        assert_eq!(images.len(), 1);
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses, stats.entries, stats.bytes), (0, 2, 1, images[0].weight()));
+
+        // Одна и та же запись для аудиторий и /images
+        assert_eq!(store.get_campus_image("UK3-left.png".to_owned()).await.unwrap().value, "bibabob0");
+        store.get_campus_images(&names).await.unwrap();
+        assert_eq!((cache.stats().hits, cache.stats().entries), (2, 1));
+        assert!(matches!(store.get_campus_images(&names[1..]).await, Err(StoreError::ImageNotFound(_))));
+
+        store.replace_image(CampusImage{name: "UK3-left.png".to_owned(), value: "pipupap0".to_owned()}).await.unwrap();
+        assert_eq!(store.get_campus_images(&names).await.unwrap()[0].value, "pipupap0");
+    }
+
+    #[actix_web::test]
+    async fn test_cache_invalidation(){
+        let (store, cache) = cached_store(CacheConfig::default());
+        let mut classroom = store.get_classroom("УК3 205".to_owned()).await.unwrap();
+        classroom.description = "Отредактировано".to_owned();
+        store.replace_classroom("УК3 205".to_owned(), classroom).await.unwrap();
+        assert_eq!(store.get_classroom("УК3 205".to_owned()).await.unwrap().description, "Отредактировано");
+        assert_eq!(cache.stats().invalidations, 1);
+
+        assert!(store.delete_classroom("УК4 104".to_owned()).await.is_err());
+        assert_eq!(cache.stats().invalidations, 1);
+
+        store.reload(json!([{"classroom": "ГУК 305", "description": "", "images": []}]).to_string(), "[]".to_owned()).await.unwrap();
+        assert_eq!(store.get_classroom_names().await.unwrap(), vec!["ГУК 305"]);
+        assert_eq!(cache.stats().invalidations, 2);
+    }
+
+    #[actix_web::test]
+    async fn test_cache_limits(){
+        let (store, cache) = cached_store(CacheConfig{max_entries: 2, ttl: Duration::from_secs(60), ..Default::default()});
+        store.get_classroom_list().await.unwrap();
+        store.get_classroom_names().await.unwrap();
+        store.get_classroom("УК3 104".to_owned()).await.unwrap();
+        assert_eq!(cache.stats().entries, 2);
+        store.get_classroom_list().await.unwrap();
+        assert_eq!(cache.stats().hits, 0);
+
+        let (store, cache) = cached_store(CacheConfig{max_entries: 10, ttl: Duration::from_millis(20), ..Default::default()});
+        store.get_classroom_list().await.unwrap();
+        std::thread::sleep(Duration::from_millis(30));
+        store.get_classroom_list().await.unwrap();
+        assert_eq!(cache.stats().hits, 0);
+        assert_eq!(cache.stats().misses, 2);
+
+        let list = store.get_classroom_list().await.unwrap();
+        let map = store.get_map("UK3-left.png".to_owned()).await.unwrap();
+        let (store, cache) = cached_store(CacheConfig{max_bytes: list.len() + map.len() - 1, ..Default::default()});
+        store.get_classroom_list().await.unwrap();
+        assert_eq!(cache.stats().bytes, list.len());
+        store.get_map("UK3-left.png".to_owned()).await.unwrap();
+        assert_eq!((cache.stats().entries, cache.stats().bytes), (1, map.len()));
+        store.get_classroom("УК3 104".to_owned()).await.unwrap();
+        assert_eq!((cache.stats().entries, cache.stats().bytes), (1, map.len()));
+    }
+}
@@ -0,0 +1,449 @@
+use serde::{Deserialize, Deserializer};
+use std::error::Error;
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+use super::blob_store::ImageStorage;
+use super::cache::CacheConfig;
+use super::mongo_client::MongoSettings;
+use super::reload::DataFiles;
+use super::seed::SeedPolicy;
+use super::server::{Server, ServerBuilder, StorageKind};
+use super::telemetry::{LogFormat, DEFAULT_LOG_LEVEL};
+
+/// Переменная среды с путем к файлу настроек
+pub const CONFIG_PATH_VAR: &str = "NAV_CONFIG";
+/// Файл настроек, который читается, если NAV_CONFIG не задана и файл существует
+pub const DEFAULT_CONFIG_PATH: &str = "navigation.toml";
+
+/// Настройки сервиса
+///
+/// # Что делает?
+/// - Читается из TOML-файла (все разделы и поля необязательны)
+/// - Переопределяется переменными среды NAV_*, см. apply_env
+/// - Проверяется целиком при запуске, чтобы сервис падал с понятной ошибкой, а не на первом запросе
+#[derive(Debug, Clone, PartialEq, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config{
+    pub server: ServerSettings,
+    pub data: DataSettings,
+    pub storage: StorageSettings,
+    pub mongo: MongoSettings,
+    pub cache: CacheSettings,
+    pub log: LogSettings,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerSettings{
+    pub host: String,
+    pub port: u16,
+    /// Число потоков-обработчиков, по умолчанию по числу ядер
+    pub workers: Option<usize>,
+    /// Токен для /admin/..., без него админский API выключен
+    pub admin_token: Option<String>,
+}
+
+impl Default for ServerSettings{
+    fn default() -> Self {
+        Self{host: "0.0.0.0".to_owned(), port: 8080, workers: None, admin_token: None}
+    }
+}
+
+/// Файлы с навигационными данными
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DataSettings{
+    pub classrooms: PathBuf,
+    pub images: PathBuf,
+    pub campus_graph: PathBuf,
+    pub maps: PathBuf,
+    /// Как часто проверять classrooms и images на изменения
+    pub reload_interval_secs: u64,
+}
+
+impl Default for DataSettings{
+    fn default() -> Self {
+        Self{
+            classrooms: "classrooms.json".into(),
+            images: "images.json".into(),
+            campus_graph: "campus_graph.json".into(),
+            maps: "maps.json".into(),
+            reload_interval_secs: 5}
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StorageSettings{
+    #[serde(deserialize_with = "parse_field")]
+    pub kind: StorageKind,
+    #[serde(deserialize_with = "parse_field")]
+    pub seed_policy: SeedPolicy,
+    /// Где хранить содержимое картинок: inline, gridfs или disk:<путь>
+    #[serde(deserialize_with = "parse_field")]
+    pub images: ImageStorage,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CacheSettings{
+    /// Сколько секунд живет ответ в кэше, 0 отключает кэш ответов
+    pub ttl_secs: u64,
+    pub max_entries: usize,
+    /// Сколько мегабайт ответов держать, 0 отключает кэш ответов
+    pub max_mb: usize,
+    /// Сколько мегабайт уменьшенных картинок держать, 0 отключает их кэш
+    pub image_cache_mb: usize,
+}
+
+impl Default for CacheSettings{
+    fn default() -> Self {
+        let defaults = CacheConfig::default();
+        Self{
+            ttl_secs: defaults.ttl.as_secs(),
+            max_entries: defaults.max_entries,
+            max_mb: defaults.max_bytes / 1024 / 1024,
+            image_cache_mb: 64}
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogSettings{
+    #[serde(deserialize_with = "parse_field")]
+    pub format: LogFormat,
+    /// Фильтр в синтаксисе RUST_LOG
+    pub level: String,
+}
+
+impl Default for LogSettings{
+    fn default() -> Self {
+        Self{format: LogFormat::default(), level: DEFAULT_LOG_LEVEL.to_owned()}
+    }
+}
+
+/// Разбирает строковое поле TOML через FromStr, как и переменные среды
+fn parse_field<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: Display,
+{
+    let value = String::deserialize(deserializer)?;
+    value.parse().map_err(serde::de::Error::custom)
+}
+
+/// Читает переменную среды через FromStr, пустое значение считается незаданным
+fn env_value<T>(env: &dyn Fn(&str) -> Option<String>, name: &str) -> Result<Option<T>, Box<dyn Error>>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    match env(name).filter(|x|!x.is_empty()) {
+        Some(value) => Ok(Some(value.parse().map_err(|e|format!("{}={}: {}", name, value, e))?)),
+        None => Ok(None),
+    }
+}
+
+/// Заменяет поле значением переменной среды, если она задана
+fn override_with<T>(field: &mut T, env: &dyn Fn(&str) -> Option<String>, name: &str) -> Result<(), Box<dyn Error>>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    if let Some(value) = env_value(env, name)? {
+        *field = value;
+    }
+    Ok(())
+}
+
+/// То же, что override_with, для необязательных полей
+fn override_optional<T>(field: &mut Option<T>, env: &dyn Fn(&str) -> Option<String>, name: &str) -> Result<(), Box<dyn Error>>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    if let Some(value) = env_value(env, name)? {
+        *field = Some(value);
+    }
+    Ok(())
+}
+
+fn read_file(path: &Path) -> Result<String, Box<dyn Error>> {
+    std::fs::read_to_string(path).map_err(|e|format!("{}: {}", path.display(), e).into())
+}
+
+impl Config{
+    /// Читает настройки процесса: файл из NAV_CONFIG (или navigation.toml, если он есть) и переменные NAV_*
+    pub fn load() -> Result<Self, Box<dyn Error>> {
+        Self::from_sources(&|name|std::env::var(name).ok())
+    }
+
+    /// Читает настройки из файла и переменных среды
+    ///
+    /// # Аргументы:
+    /// - env: источник переменных среды, в тестах подменяется
+    ///
+    /// # Примечание:
+    /// Явно указанный в NAV_CONFIG файл обязан существовать, navigation.toml по умолчанию - нет
+    pub fn from_sources(env: &dyn Fn(&str) -> Option<String>) -> Result<Self, Box<dyn Error>> {
+        let mut config = match env(CONFIG_PATH_VAR).filter(|x|!x.is_empty()) {
+            Some(path) => Self::from_toml(&read_file(Path::new(&path))?).map_err(|e|format!("{}: {}", path, e))?,
+            None if Path::new(DEFAULT_CONFIG_PATH).exists() => Self::from_toml(&read_file(Path::new(DEFAULT_CONFIG_PATH))?)
+                .map_err(|e|format!("{}: {}", DEFAULT_CONFIG_PATH, e))?,
+            None => Self::default(),
+        };
+        config.apply_env(env)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn from_toml(data: &str) -> Result<Self, Box<dyn Error>> {
+        Ok(toml::from_str(data)?)
+    }
+
+    /// Переопределяет настройки переменными среды
+    ///
+    /// # Примечание:
+    /// Названия переменных перечислены в README, раздел "Настройки"
+    pub fn apply_env(&mut self, env: &dyn Fn(&str) -> Option<String>) -> Result<(), Box<dyn Error>> {
+        override_with(&mut self.server.host, env, "NAV_HOST")?;
+        override_with(&mut self.server.port, env, "NAV_PORT")?;
+        override_optional(&mut self.server.workers, env, "NAV_WORKERS")?;
+        override_optional(&mut self.server.admin_token, env, "NAV_ADMIN_TOKEN")?;
+
+        override_with(&mut self.data.classrooms, env, "NAV_CLASSROOMS_FILE")?;
+        override_with(&mut self.data.images, env, "NAV_IMAGES_FILE")?;
+        override_with(&mut self.data.campus_graph, env, "NAV_CAMPUS_GRAPH_FILE")?;
+        override_with(&mut self.data.maps, env, "NAV_MAPS_FILE")?;
+        override_with(&mut self.data.reload_interval_secs, env, "NAV_RELOAD_INTERVAL_SECS")?;
+
+        override_with(&mut self.storage.kind, env, "NAV_STORAGE")?;
+        override_with(&mut self.storage.seed_policy, env, "NAV_SEED_POLICY")?;
+        override_with(&mut self.storage.images, env, "NAV_IMAGE_STORAGE")?;
+
+        override_optional(&mut self.mongo.uri, env, "NAV_MONGO_URI")?;
+        override_with(&mut self.mongo.host, env, "NAV_MONGO_HOST")?;
+        override_with(&mut self.mongo.port, env, "NAV_MONGO_PORT")?;
+        override_optional(&mut self.mongo.username, env, "NAV_MONGO_USERNAME")?;
+        override_optional(&mut self.mongo.password, env, "NAV_MONGO_PASSWORD")?;
+        override_optional(&mut self.mongo.password_file, env, "NAV_MONGO_PASSWORD_FILE")?;
+        override_optional(&mut self.mongo.auth_source, env, "NAV_MONGO_AUTH_SOURCE")?;
+        override_optional(&mut self.mongo.replica_set, env, "NAV_MONGO_REPLICA_SET")?;
+        override_optional(&mut self.mongo.tls, env, "NAV_MONGO_TLS")?;
+        override_optional(&mut self.mongo.tls_ca_file, env, "NAV_MONGO_TLS_CA_FILE")?;
+        override_optional(&mut self.mongo.tls_cert_key_file, env, "NAV_MONGO_TLS_CERT_KEY_FILE")?;
+        override_optional(&mut self.mongo.min_pool_size, env, "NAV_MONGO_MIN_POOL_SIZE")?;
+        override_optional(&mut self.mongo.max_pool_size, env, "NAV_MONGO_MAX_POOL_SIZE")?;
+        override_optional(&mut self.mongo.connect_timeout_secs, env, "NAV_MONGO_CONNECT_TIMEOUT_SECS")?;
+        override_optional(&mut self.mongo.server_selection_timeout_secs, env, "NAV_MONGO_SERVER_SELECTION_TIMEOUT_SECS")?;
+        override_with(&mut self.mongo.database, env, "NAV_DB_NAME")?;
+        override_with(&mut self.mongo.classrooms_collection, env, "NAV_CLASSROOMS_COLLECTION")?;
+        override_with(&mut self.mongo.images_collection, env, "NAV_IMAGES_COLLECTION")?;
+        override_with(&mut self.mongo.metadata_collection, env, "NAV_METADATA_COLLECTION")?;
+        override_with(&mut self.mongo.startup_retries, env, "NAV_MONGO_STARTUP_RETRIES")?;
+        override_with(&mut self.mongo.retry_max_delay_secs, env, "NAV_MONGO_RETRY_MAX_DELAY_SECS")?;
+        override_with(&mut self.mongo.fallback, env, "NAV_MONGO_FALLBACK")?;
+        override_with(&mut self.mongo.snapshot_refresh_secs, env, "NAV_MONGO_SNAPSHOT_REFRESH_SECS")?;
+
+        override_with(&mut self.cache.ttl_secs, env, "NAV_CACHE_TTL_SECS")?;
+        override_with(&mut self.cache.max_entries, env, "NAV_CACHE_MAX_ENTRIES")?;
+        override_with(&mut self.cache.max_mb, env, "NAV_CACHE_MAX_MB")?;
+        override_with(&mut self.cache.image_cache_mb, env, "NAV_IMAGE_CACHE_MB")?;
+
+        override_with(&mut self.log.format, env, "NAV_LOG_FORMAT")?;
+        override_with(&mut self.log.level, env, "NAV_LOG_LEVEL")?;
+        Ok(())
+    }
+
+    /// Проверяет значения, которые разобрались, но не имеют смысла
+    pub fn validate(&self) -> Result<(), Box<dyn Error>> {
+        let mut errors = Vec::new();
+        if self.server.host.trim().is_empty() {
+            errors.push("server.host is empty".to_owned());
+        }
+        if self.server.port == 0 {
+            errors.push("server.port must not be 0".to_owned());
+        }
+        if self.server.workers == Some(0) {
+            errors.push("server.workers must be at least 1".to_owned());
+        }
+        if self.data.reload_interval_secs == 0 {
+            errors.push("data.reload_interval_secs must be at least 1".to_owned());
+        }
+        if self.mongo.port == 0 {
+            errors.push("mongo.port must not be 0".to_owned());
+        }
+        if let Some(uri) = &self.mongo.uri {
+            if !uri.starts_with("mongodb://") && !uri.starts_with("mongodb+srv://") {
+                errors.push("mongo.uri must start with mongodb:// or mongodb+srv://".to_owned());
+            }
+        }
+        if self.mongo.password.is_some() && self.mongo.password_file.is_some() {
+            errors.push("mongo.password and mongo.password_file are both set".to_owned());
+        }
+        if let (Some(min), Some(max)) = (self.mongo.min_pool_size, self.mongo.max_pool_size) {
+            if min > max {
+                errors.push(format!("mongo.min_pool_size ({}) is greater than mongo.max_pool_size ({})", min, max));
+            }
+        }
+        if self.mongo.max_pool_size == Some(0) {
+            errors.push("mongo.max_pool_size must be at least 1".to_owned());
+        }
+        if self.mongo.retry_max_delay_secs == 0 {
+            errors.push("mongo.retry_max_delay_secs must be at least 1".to_owned());
+        }
+        if self.mongo.snapshot_refresh_secs == 0 {
+            errors.push("mongo.snapshot_refresh_secs must be at least 1".to_owned());
+        }
+        let names = [
+            ("mongo.host", &self.mongo.host),
+            ("mongo.database", &self.mongo.database),
+            ("mongo.classrooms_collection", &self.mongo.classrooms_collection),
+            ("mongo.images_collection", &self.mongo.images_collection),
+            ("mongo.metadata_collection", &self.mongo.metadata_collection),
+        ];
+        for (name, value) in names {
+            if value.trim().is_empty() {
+                errors.push(format!("{} is empty", name));
+            }
+        }
+        if errors.is_empty() {Ok(())} else {Err(errors.join("; ").into())}
+    }
+
+    /// Читает файлы с данными и настраивает сервер
+    ///
+    /// # Примечание:
+    /// Ошибка называет файл, который не прочитался
+    pub fn server_builder(&self) -> Result<ServerBuilder, Box<dyn Error>> {
+        let mut builder = Server::builder()
+            .host(self.server.host.to_owned())
+            .port(self.server.port)
+            .classroom_data(read_file(&self.data.classrooms)?)
+            .image_data(read_file(&self.data.images)?)
+            .campus_graph(read_file(&self.data.campus_graph)?)
+            .maps(read_file(&self.data.maps)?)
+            .storage(self.storage.kind)
+            .seed_policy(self.storage.seed_policy)
+            .image_storage(self.storage.images.to_owned())
+            .mongo(self.mongo.to_owned())
+            .data_files(DataFiles{
+                classrooms: self.data.classrooms.to_owned(),
+                images: self.data.images.to_owned()})
+            .reload_interval(Duration::from_secs(self.data.reload_interval_secs))
+            .variant_cache_size(self.cache.image_cache_mb * 1024 * 1024);
+        if let Some(workers) = self.server.workers {
+            builder = builder.workers(workers);
+        }
+        if let Some(token) = self.server.admin_token.as_ref().filter(|x|!x.is_empty()) {
+            builder = builder.admin_token(token.to_owned());
+        }
+        if self.cache.ttl_secs > 0 && self.cache.max_entries > 0 && self.cache.max_mb > 0 {
+            builder = builder.cache(CacheConfig{
+                max_entries: self.cache.max_entries,
+                max_bytes: self.cache.max_mb * 1024 * 1024,
+                ttl: Duration::from_secs(self.cache.ttl_secs)});
+        }
+        Ok(builder)
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use std::collections::HashMap;
+    use super::*;
+
+    fn env_of(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars: HashMap<String, String> = vars.iter().map(|(k, v)|(k.to_string(), v.to_string())).collect();
+        move |name|vars.get(name).cloned()
+    }
+
+    #[test]
+    fn test_defaults(){
+        let config = Config::from_toml("").unwrap();
+        assert_eq!(config, Config::default());
+        assert_eq!((config.server.host.as_str(), config.server.port), ("0.0.0.0", 8080));
+        assert_eq!(config.mongo.database, "navigationData");
+        assert_eq!(config.data.images, PathBuf::from("images.json"));
+        assert!(config.validate().is_ok());
+        assert_eq!(Config::from_toml(include_str!("../navigation.example.toml")).unwrap(), Config::default());
+    }
+
+    #[test]
+    fn test_file_and_env(){
+        let mut config = Config::from_toml(r#"
+            [server]
+            port = 9000
+            workers = 2
+
+            [storage]
+            kind = "memory"
+            seed_policy = "never"
+            images = "disk:/data/images"
+
+            [mongo]
+            database = "navigationTest"
+
+            [log]
+            format = "json"
+        "#).unwrap();
+        assert_eq!(config.server.workers, Some(2));
+        assert_eq!(config.storage.kind, StorageKind::Memory);
+        assert_eq!(config.storage.seed_policy, SeedPolicy::Never);
+        assert_eq!(config.storage.images, ImageStorage::Disk("/data/images".into()));
+        assert_eq!(config.log.format, LogFormat::Json);
+
+        let env = env_of(&[
+            ("NAV_PORT", "9100"),
+            ("NAV_DB_NAME", "navigation"),
+            ("NAV_MONGO_PASSWORD", "secret"),
+            ("NAV_MONGO_URI", "mongodb+srv://cluster.example.com"),
+            ("NAV_MONGO_TLS", "true"),
+            ("NAV_WORKERS", "")]);
+        config.apply_env(&env).unwrap();
+        assert_eq!(config.server.port, 9100);
+        assert_eq!(config.server.workers, Some(2));
+        assert_eq!(config.mongo.database, "navigation");
+        assert_eq!(config.mongo.password.as_deref(), Some("secret"));
+        assert_eq!(config.mongo.uri.as_deref(), Some("mongodb+srv://cluster.example.com"));
+        assert_eq!(config.mongo.tls, Some(true));
+    }
+
+    #[test]
+    fn test_invalid_values(){
+        assert!(Config::from_toml("[server]\nport = 70000").is_err());
+        assert!(Config::from_toml("[server]\nprot = 80").is_err());
+        assert!(Config::from_toml("[storage]\nkind = \"sqlite\"").is_err());
+
+        let error = Config::default().apply_env(&env_of(&[("NAV_PORT", "eighty")])).unwrap_err();
+        assert!(error.to_string().starts_with("NAV_PORT=eighty"), "{}", error);
+
+        let mut config = Config::default();
+        config.mongo.uri = Some("mongo://db".to_owned());
+        config.mongo.password = Some("secret".to_owned());
+        config.mongo.password_file = Some("/run/secrets/mongo".into());
+        config.mongo.min_pool_size = Some(10);
+        config.mongo.max_pool_size = Some(5);
+        let error = config.validate().unwrap_err().to_string();
+        assert!(error.contains("mongo.uri") && error.contains("password_file") && error.contains("min_pool_size"), "{}", error);
+
+        let mut config = Config::default();
+        config.server.workers = Some(0);
+        config.mongo.database = " ".to_owned();
+        let error = config.validate().unwrap_err().to_string();
+        assert!(error.contains("server.workers") && error.contains("mongo.database"), "{}", error);
+
+        let error = Config::from_sources(&env_of(&[(CONFIG_PATH_VAR, "/nonexistent/navigation.toml")])).unwrap_err();
+        assert!(error.to_string().contains("/nonexistent/navigation.toml"));
+    }
+
+    #[test]
+    fn test_missing_data_file(){
+        let mut config = Config::default();
+        config.data.images = "/nonexistent/images.json".into();
+        let error = config.server_builder().err().unwrap().to_string();
+        assert!(error.starts_with("/nonexistent/images.json"), "{}", error);
+    }
+}
@@ -0,0 +1,224 @@
+use actix_web::http::{header, StatusCode};
+use actix_web::{HttpResponse, ResponseError, web};
+use std::fmt;
+
+/// Ошибка хранилища навигационных данных
+#[derive(Debug, Clone, PartialEq)]
+pub enum StoreError{
+    /// Аудитории с таким именем нет
+    ClassroomNotFound(String),
+    /// Картинки с таким названием нет
+    ImageNotFound(String),
+    /// Запись с таким именем уже есть
+    Conflict(String),
+    /// Данные не подходят под схему ClassroomData/CampusImage
+    InvalidData(String),
+    /// База недоступна: нет соединения, не прошла авторизация, истек таймаут
+    Unavailable(String),
+    /// Все остальное
+    Internal(String),
+}
+
+impl fmt::Display for StoreError{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ClassroomNotFound(name) => write!(f, "Classroom not found: {}", name),
+            Self::ImageNotFound(name) => write!(f, "Image not found: {}", name),
+            Self::Conflict(message) => write!(f, "Conflict: {}", message),
+            Self::InvalidData(message) => write!(f, "Invalid data: {}", message),
+            Self::Unavailable(message) => write!(f, "Database unavailable: {}", message),
+            Self::Internal(message) => write!(f, "Internal error: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for StoreError {}
+
+impl From<mongodb::error::Error> for StoreError{
+    fn from(e: mongodb::error::Error) -> Self {
+        use mongodb::error::ErrorKind;
+        match *e.kind {
+            ErrorKind::Io(_)
+            | ErrorKind::ServerSelection{..}
+            | ErrorKind::ConnectionPoolCleared{..}
+            | ErrorKind::DnsResolve{..}
+            | ErrorKind::Authentication{..} => Self::Unavailable(e.to_string()),
+            _ => Self::Internal(e.to_string()),
+        }
+    }
+}
+
+/// Ошибки сериализации ответов - это ошибки сервера. Входные данные
+/// разбираются через parse_classroom_data/parse_image_data, которые выдают InvalidData
+impl From<serde_json::Error> for StoreError{
+    fn from(e: serde_json::Error) -> Self {
+        Self::Internal(e.to_string())
+    }
+}
+
+/// Ошибки файловой системы - у хранилища картинок на диске
+impl From<std::io::Error> for StoreError{
+    fn from(e: std::io::Error) -> Self {
+        Self::Unavailable(e.to_string())
+    }
+}
+
+impl From<mongodb::bson::de::Error> for StoreError{
+    fn from(e: mongodb::bson::de::Error) -> Self {
+        Self::Internal(e.to_string())
+    }
+}
+
+impl From<mongodb::bson::ser::Error> for StoreError{
+    fn from(e: mongodb::bson::ser::Error) -> Self {
+        Self::Internal(e.to_string())
+    }
+}
+
+/// Тело ответа с ошибкой
+#[derive(Debug, serde::Serialize, serde::Deserialize, utoipa::ToSchema, Clone, PartialEq)]
+pub struct ErrorBody{
+    /// Машиночитаемый код, по которому бот выбирает, что ответить пользователю
+    pub code: String,
+    pub message: String,
+    /// Дополнительные данные, например подсказки did_you_mean
+    pub details: serde_json::Value,
+}
+
+/// Ошибка обработчика, отдается клиенту как JSON {code, message, details}
+///
+/// # Коды:
+/// - classroom_not_found, image_not_found, route_not_found, not_found - 404
+/// - bad_request, invalid_data - 400 (422, если данные уже в хранилище и не обрабатываются)
+/// - unauthorized - 401, forbidden - 403, not_acceptable - 406, conflict - 409
+/// - db_unavailable, not_ready - 503, internal - 500
+#[derive(Debug, Clone)]
+pub struct ApiError{
+    status: StatusCode,
+    body: Box<ErrorBody>,
+}
+
+impl ApiError{
+    pub fn new(status: StatusCode, code: &str, message: impl Into<String>) -> Self {
+        Self{
+            status,
+            body: Box::new(ErrorBody{code: code.to_owned(), message: message.into(), details: serde_json::Value::Null})}
+    }
+
+    /// Добавляет к ошибке дополнительные данные
+    pub fn with_details(mut self, details: serde_json::Value) -> Self {
+        self.body.details = details;
+        self
+    }
+
+    pub fn bad_request(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, "bad_request", message)
+    }
+
+    pub fn not_found(code: &str, message: impl Into<String>) -> Self {
+        Self::new(StatusCode::NOT_FOUND, code, message)
+    }
+
+    pub fn conflict(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::CONFLICT, "conflict", message)
+    }
+
+    pub fn internal(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::INTERNAL_SERVER_ERROR, "internal", message)
+    }
+
+    pub fn code(&self) -> &str {
+        &self.body.code
+    }
+}
+
+impl fmt::Display for ApiError{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.body.code, self.body.message)
+    }
+}
+
+impl ResponseError for ApiError{
+    fn status_code(&self) -> StatusCode {
+        self.status
+    }
+
+    /// # Примечание:
+    /// Ошибки 5xx пишутся в лог с сообщением, остальные видны только по коду в строке запроса
+    fn error_response(&self) -> HttpResponse {
+        if self.status.is_server_error() {
+            tracing::error!(code = %self.body.code, message = %self.body.message, "request error");
+        }
+        let mut response = HttpResponse::build(self.status);
+        if self.status == StatusCode::UNAUTHORIZED {
+            response.insert_header((header::WWW_AUTHENTICATE, "Bearer"));
+        }
+        response.json(self.body.as_ref())
+    }
+}
+
+impl From<StoreError> for ApiError{
+    fn from(e: StoreError) -> Self {
+        let message = e.to_string();
+        match e {
+            StoreError::ClassroomNotFound(_) => Self::not_found("classroom_not_found", message),
+            StoreError::ImageNotFound(_) => Self::not_found("image_not_found", message),
+            StoreError::Conflict(_) => Self::conflict(message),
+            StoreError::InvalidData(_) => Self::new(StatusCode::BAD_REQUEST, "invalid_data", message),
+            StoreError::Unavailable(_) => Self::new(StatusCode::SERVICE_UNAVAILABLE, "db_unavailable", message),
+            StoreError::Internal(_) => Self::internal(message),
+        }
+    }
+}
+
+/// Отдает ошибки разбора запроса (query, path, JSON) в том же формате, что и остальные ошибки
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.app_data(web::QueryConfig::default()
+            .error_handler(|e, _|ApiError::bad_request(e.to_string()).into()))
+        .app_data(web::PathConfig::default()
+            .error_handler(|e, _|ApiError::bad_request(e.to_string()).into()))
+        .app_data(web::JsonConfig::default()
+            .error_handler(|e, _|ApiError::bad_request(e.to_string()).into()));
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn test_store_error_status(){
+        let cases = [
+            (StoreError::ClassroomNotFound("УТК".to_owned()), StatusCode::NOT_FOUND, "classroom_not_found"),
+            (StoreError::ImageNotFound("a.png".to_owned()), StatusCode::NOT_FOUND, "image_not_found"),
+            (StoreError::Conflict("exists".to_owned()), StatusCode::CONFLICT, "conflict"),
+            (StoreError::InvalidData("bad".to_owned()), StatusCode::BAD_REQUEST, "invalid_data"),
+            (StoreError::Unavailable("down".to_owned()), StatusCode::SERVICE_UNAVAILABLE, "db_unavailable"),
+            (StoreError::Internal("oops".to_owned()), StatusCode::INTERNAL_SERVER_ERROR, "internal"),
+        ];
+        for (error, status, code) in cases {
+            let error = ApiError::from(error);
+            assert_eq!(error.status_code(), status);
+            assert_eq!(error.code(), code);
+        }
+    }
+
+    #[test]
+    fn test_json_error_kind(){
+        let error = serde_json::from_str::<Vec<String>>("{").unwrap_err();
+        assert!(matches!(StoreError::from(error), StoreError::Internal(_)));
+        assert!(matches!(crate::models::parse_classroom_data("{"), Err(StoreError::InvalidData(_))));
+        assert!(matches!(crate::models::parse_image_data("[1]"), Err(StoreError::InvalidData(_))));
+    }
+
+    #[actix_web::test]
+    async fn test_error_body(){
+        let error = ApiError::not_found("classroom_not_found", "Classroom not found: УТК")
+            .with_details(serde_json::json!({"did_you_mean": ["УК3 104"]}));
+        let response = error.error_response();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let body = actix_web::body::to_bytes(response.into_body()).await.unwrap();
+        let body: ErrorBody = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body.code, "classroom_not_found");
+        assert_eq!(body.details["did_you_mean"][0], "УК3 104");
+    }
+}
@@ -0,0 +1,620 @@
+use async_trait::async_trait;
+use futures::future::BoxFuture;
+use std::future::Future;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::Notify;
+use super::error::StoreError;
+use super::memory_store::MemoryStore;
+use super::models::{CampusImage, ClassroomData};
+use super::store::{Dataset, Degradation, NavigationStore, StoreStatus};
+
+/// Паузы между попытками: растут вдвое от initial, но не больше max
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Backoff{
+    pub initial: Duration,
+    pub max: Duration,
+}
+
+impl Backoff{
+    /// Паузы от одной секунды до max
+    pub fn up_to(max: Duration) -> Self {
+        Self{initial: Duration::from_secs(1).min(max), max}
+    }
+
+    /// Пауза перед повтором номер attempt, считая с нуля
+    pub fn delay(&self, attempt: u32) -> Duration {
+        self.initial
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max)
+    }
+}
+
+/// Подключается к хранилищу, повторяя попытки, пока оно недоступно
+///
+/// # Аргументы:
+/// - retries: сколько раз повторить после первой неудачи
+/// - backoff: паузы между попытками
+/// - connect: попытка подключения
+///
+/// # Примечание:
+/// Повторяются только ошибки StoreError::Unavailable, остальные (нет пароля,
+/// битые данные) не исправятся сами и выдаются сразу
+pub async fn connect_with_retry<T, F, Fut>(retries: u32, backoff: Backoff, connect: F) -> Result<T, StoreError>
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = Result<T, StoreError>>,
+{
+    let mut attempt = 0;
+    loop {
+        match connect().await {
+            Err(StoreError::Unavailable(message)) if attempt < retries => {
+                let delay = backoff.delay(attempt);
+                attempt += 1;
+                tracing::warn!(attempt, retries, delay_secs = delay.as_secs_f64(), error = %message, "database unavailable, retrying");
+                tokio::time::sleep(delay).await;
+            },
+            result => return result,
+        }
+    }
+}
+
+/// Хранилище, которое переживает недоступность базы
+///
+/// # Что делает?
+/// - Держит в памяти копию последних данных, успешно загруженных из базы
+/// - Пока база не отвечает, отдает данные из копии, а правки отклоняет с 503
+/// - В фоне (см. run) проверяет базу с растущими паузами и возвращается к ней, когда она ответит
+/// - Пока база доступна, обновляет копию раз в refresh_interval и повторяет в ней правки
+///
+/// # Примечание:
+/// Копия обновляется, только если в базе сменилась версия набора данных или число аудиторий
+/// и картинок, поэтому правки других реплик, которые их не меняют, в копию не попадают
+pub struct FallbackStore{
+    primary: Arc<dyn NavigationStore>,
+    snapshot: RwLock<Arc<MemoryStore>>,
+    outage: RwLock<Option<Degradation>>,
+    /// Будит фоновую проверку, когда база перестала отвечать
+    outage_started: Notify,
+    backoff: Backoff,
+    refresh_interval: Duration,
+}
+
+impl FallbackStore{
+    /// Создает хранилище и сразу снимает копию данных из базы
+    ///
+    /// # Аргументы:
+    /// - primary: основное хранилище
+    /// - backoff: паузы между проверками недоступной базы
+    /// - refresh_interval: как часто обновлять копию, пока база доступна
+    pub async fn new(primary: Arc<dyn NavigationStore>, backoff: Backoff, refresh_interval: Duration) -> Result<Self, StoreError> {
+        let snapshot = MemoryStore::from_dataset(primary.export().await?)?;
+        Ok(Self{
+            primary,
+            snapshot: RwLock::new(Arc::new(snapshot)),
+            outage: RwLock::new(None),
+            outage_started: Notify::new(),
+            backoff,
+            refresh_interval})
+    }
+
+    /// Выдает причину, по которой данные отдаются из копии, или None, если база отвечает
+    pub fn degraded(&self) -> Option<Degradation> {
+        self.outage.read().unwrap().clone()
+    }
+
+    /// Снимает новую копию данных из базы
+    pub async fn refresh(&self) -> Result<(), StoreError> {
+        let snapshot = MemoryStore::from_dataset(self.primary.export().await?)?;
+        *self.snapshot.write().unwrap() = Arc::new(snapshot);
+        Ok(())
+    }
+
+    /// Снимает новую копию, если данные в базе изменились
+    ///
+    /// # Аргументы:
+    /// - status: состояние базы, только что полученное из нее
+    ///
+    /// # Примечание:
+    /// Полная выгрузка скачивает все картинки, поэтому сначала сравниваются версия набора данных
+    /// со счетчиком правок через админский API и число аудиторий и картинок в базе и в копии.
+    /// Если база не заполнялась из файлов и версии у нее нет, копия обновляется каждый раз
+    async fn refresh_if_changed(&self, status: &StoreStatus) -> Result<(), StoreError> {
+        let current = self.snapshot().status().await?;
+        if status.dataset.is_some() && status.dataset == current.dataset
+            && (status.classrooms, status.images) == (current.classrooms, current.images) {
+            return Ok(());
+        }
+        self.refresh().await
+    }
+
+    /// Проверяет базу и обновляет копию, если данные изменились
+    ///
+    /// # Примечание:
+    /// Если база не отвечает, переключается на копию, а если снова ответила - возвращается к ней
+    pub async fn check(&self) -> Result<(), StoreError> {
+        let result = match self.primary.status().await {
+            Ok(status) => self.refresh_if_changed(&status).await,
+            Err(e) => Err(e),
+        };
+        match &result {
+            Ok(()) => {
+                if let Some(outage) = self.outage.write().unwrap().take() {
+                    tracing::info!(since = outage.since, "database is reachable again, leaving the in-memory copy");
+                }
+            },
+            Err(StoreError::Unavailable(message)) => self.fall_back(message),
+            Err(e) => tracing::warn!(error = %e, "in-memory copy not refreshed"),
+        }
+        result
+    }
+
+    /// Проверяет базу в фоне, не завершается
+    ///
+    /// # Примечание:
+    /// Пока база доступна, проверка идет раз в refresh_interval, а после сбоя -
+    /// с паузами из backoff, пока база не ответит
+    pub async fn run(&self) {
+        let mut attempt = 0;
+        loop {
+            if self.degraded().is_some() {
+                tokio::time::sleep(self.backoff.delay(attempt)).await;
+            } else {
+                let tick = tokio::time::sleep(self.refresh_interval);
+                let outage = self.outage_started.notified();
+                futures::pin_mut!(tick, outage);
+                if let futures::future::Either::Right(_) = futures::future::select(tick, outage).await {
+                    attempt = 0;
+                    continue;
+                }
+            }
+            match self.check().await {
+                Ok(()) => attempt = 0,
+                Err(_) => attempt = attempt.saturating_add(1),
+            }
+        }
+    }
+
+    fn snapshot(&self) -> Arc<MemoryStore> {
+        self.snapshot.read().unwrap().clone()
+    }
+
+    /// Переключается на копию, если еще не переключились
+    fn fall_back(&self, message: &str) {
+        let mut outage = self.outage.write().unwrap();
+        match outage.as_mut() {
+            Some(val) => val.error = message.to_owned(),
+            None => {
+                tracing::warn!(error = %message, "database unavailable, serving the in-memory copy");
+                let since = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+                *outage = Some(Degradation{since, error: message.to_owned()});
+                self.outage_started.notify_one();
+            },
+        }
+    }
+
+    /// Читает из базы, а если она недоступна - из копии
+    async fn read<T, F>(&self, call: F) -> Result<T, StoreError>
+    where
+        F: for<'a> Fn(&'a dyn NavigationStore) -> BoxFuture<'a, Result<T, StoreError>> + Send + Sync,
+    {
+        if self.degraded().is_none() {
+            match call(self.primary.as_ref()).await {
+                Err(StoreError::Unavailable(message)) => self.fall_back(&message),
+                result => return result,
+            }
+        }
+        let snapshot = self.snapshot();
+        call(snapshot.as_ref()).await
+    }
+
+    /// Пишет в базу и повторяет правку в копии, пока база недоступна - отклоняет правку
+    async fn write<F>(&self, call: F) -> Result<(), StoreError>
+    where
+        F: for<'a> Fn(&'a dyn NavigationStore) -> BoxFuture<'a, Result<(), StoreError>> + Send + Sync,
+    {
+        if let Some(outage) = self.degraded() {
+            return Err(StoreError::Unavailable(format!("read-only until the database is back: {}", outage.error)));
+        }
+        match call(self.primary.as_ref()).await {
+            Err(StoreError::Unavailable(message)) => {
+                self.fall_back(&message);
+                Err(StoreError::Unavailable(message))
+            },
+            Err(e) => Err(e),
+            Ok(()) => {
+                let snapshot = self.snapshot();
+                if let Err(e) = call(snapshot.as_ref()).await {
+                    tracing::warn!(error = %e, "edit not applied to the in-memory copy, it will be fixed on the next refresh");
+                }
+                Ok(())
+            },
+        }
+    }
+}
+
+#[async_trait]
+impl NavigationStore for FallbackStore{
+    async fn get_classroom_list(&self) -> Result<String, StoreError> {
+        self.read(|store|store.get_classroom_list()).await
+    }
+
+    async fn get_classroom_names(&self) -> Result<Vec<String>, StoreError> {
+        self.read(|store|store.get_classroom_names()).await
+    }
+
+    async fn get_classroom(&self, classroom_name: String) -> Result<ClassroomData, StoreError> {
+        self.read(|store|store.get_classroom(classroom_name.to_owned())).await
+    }
+
+    async fn get_classroom_data(&self, classroom_name: String) -> Result<String, StoreError> {
+        self.read(|store|store.get_classroom_data(classroom_name.to_owned())).await
+    }
+
+    async fn get_campus_image(&self, image_name: String) -> Result<CampusImage, StoreError> {
+        self.read(|store|store.get_campus_image(image_name.to_owned())).await
+    }
+
+    async fn get_campus_images(&self, image_names: &[String]) -> Result<Vec<CampusImage>, StoreError> {
+        let image_names = image_names.to_vec();
+        self.read(|store|{
+            let image_names = image_names.clone();
+            Box::pin(async move {store.get_campus_images(&image_names).await})
+        }).await
+    }
+
+    async fn has_image(&self, image_name: String) -> Result<bool, StoreError> {
+        self.read(|store|store.has_image(image_name.to_owned())).await
+    }
+
+    async fn get_map(&self, image_name: String) -> Result<String, StoreError> {
+        self.read(|store|store.get_map(image_name.to_owned())).await
+    }
+
+    async fn get_classrooms_with_image(&self, image_name: String) -> Result<Vec<String>, StoreError> {
+        self.read(|store|store.get_classrooms_with_image(image_name.to_owned())).await
+    }
+
+    async fn insert_classroom(&self, classroom: ClassroomData) -> Result<(), StoreError> {
+        self.write(|store|store.insert_classroom(classroom.clone())).await
+    }
+
+    async fn replace_classroom(&self, classroom_name: String, classroom: ClassroomData) -> Result<(), StoreError> {
+        self.write(|store|store.replace_classroom(classroom_name.to_owned(), classroom.clone())).await
+    }
+
+    async fn delete_classroom(&self, classroom_name: String) -> Result<(), StoreError> {
+        self.write(|store|store.delete_classroom(classroom_name.to_owned())).await
+    }
+
+    async fn insert_image(&self, image: CampusImage) -> Result<(), StoreError> {
+        self.write(|store|store.insert_image(image.clone())).await
+    }
+
+    async fn replace_image(&self, image: CampusImage) -> Result<(), StoreError> {
+        self.write(|store|store.replace_image(image.clone())).await
+    }
+
+    async fn delete_image(&self, image_name: String) -> Result<(), StoreError> {
+        self.write(|store|store.delete_image(image_name.to_owned())).await
+    }
+
+    /// # Примечание:
+    /// Копия после перезагрузки снимается заново из базы, а не из файлов,
+    /// чтобы в ней остались правки через админский API
+    async fn reload(&self, classroom_data: String, image_data: String) -> Result<(), StoreError> {
+        if let Some(outage) = self.degraded() {
+            return Err(StoreError::Unavailable(format!("reload postponed until the database is back: {}", outage.error)));
+        }
+        match self.primary.reload(classroom_data, image_data).await {
+            Err(StoreError::Unavailable(message)) => {
+                self.fall_back(&message);
+                return Err(StoreError::Unavailable(message));
+            },
+            result => result?,
+        }
+        if let Err(e) = self.refresh().await {
+            tracing::warn!(error = %e, "in-memory copy not refreshed after reload");
+        }
+        Ok(())
+    }
+
+    async fn export(&self) -> Result<Dataset, StoreError> {
+        self.read(|store|store.export()).await
+    }
+
+    /// # Примечание:
+    /// Пока данные отдаются из копии, выдает ее состояние с заполненным degraded, а не ошибку:
+    /// сервис продолжает отвечать, и оркестратору не нужно убирать его из балансировки
+    async fn status(&self) -> Result<StoreStatus, StoreError> {
+        if self.degraded().is_none() {
+            match self.primary.status().await {
+                Err(StoreError::Unavailable(message)) => self.fall_back(&message),
+                result => return result,
+            }
+        }
+        let mut status = self.snapshot().status().await?;
+        status.degraded = self.degraded();
+        Ok(status)
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use serde_json::json;
+    use serial_test::serial;
+    use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+    use actix_service::Service;
+    use actix_web::http::StatusCode;
+    use actix_web::test::TestRequest;
+    use super::*;
+    use super::super::server::Server;
+
+    /// Хранилище в памяти, которое можно "выключить", как упавшую базу
+    struct FlakyStore{
+        inner: MemoryStore,
+        down: AtomicBool,
+        exports: AtomicU32,
+    }
+
+    impl FlakyStore{
+        fn check(&self) -> Result<(), StoreError> {
+            match self.down.load(Ordering::SeqCst) {
+                true => Err(StoreError::Unavailable("connection refused".to_owned())),
+                false => Ok(()),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl NavigationStore for FlakyStore{
+        async fn get_classroom_list(&self) -> Result<String, StoreError> {
+            self.check()?;
+            self.inner.get_classroom_list().await
+        }
+
+        async fn get_classroom_names(&self) -> Result<Vec<String>, StoreError> {
+            self.check()?;
+            self.inner.get_classroom_names().await
+        }
+
+        async fn get_classroom(&self, classroom_name: String) -> Result<ClassroomData, StoreError> {
+            self.check()?;
+            self.inner.get_classroom(classroom_name).await
+        }
+
+        async fn get_classroom_data(&self, classroom_name: String) -> Result<String, StoreError> {
+            self.check()?;
+            self.inner.get_classroom_data(classroom_name).await
+        }
+
+        async fn get_campus_image(&self, image_name: String) -> Result<CampusImage, StoreError> {
+            self.check()?;
+            self.inner.get_campus_image(image_name).await
+        }
+
+        async fn get_campus_images(&self, image_names: &[String]) -> Result<Vec<CampusImage>, StoreError> {
+            self.check()?;
+            self.inner.get_campus_images(image_names).await
+        }
+
+        async fn has_image(&self, image_name: String) -> Result<bool, StoreError> {
+            self.check()?;
+            self.inner.has_image(image_name).await
+        }
+
+        async fn get_map(&self, image_name: String) -> Result<String, StoreError> {
+            self.check()?;
+            self.inner.get_map(image_name).await
+        }
+
+        async fn get_classrooms_with_image(&self, image_name: String) -> Result<Vec<String>, StoreError> {
+            self.check()?;
+            self.inner.get_classrooms_with_image(image_name).await
+        }
+
+        async fn insert_classroom(&self, classroom: ClassroomData) -> Result<(), StoreError> {
+            self.check()?;
+            self.inner.insert_classroom(classroom).await
+        }
+
+        async fn replace_classroom(&self, classroom_name: String, classroom: ClassroomData) -> Result<(), StoreError> {
+            self.check()?;
+            self.inner.replace_classroom(classroom_name, classroom).await
+        }
+
+        async fn delete_classroom(&self, classroom_name: String) -> Result<(), StoreError> {
+            self.check()?;
+            self.inner.delete_classroom(classroom_name).await
+        }
+
+        async fn insert_image(&self, image: CampusImage) -> Result<(), StoreError> {
+            self.check()?;
+            self.inner.insert_image(image).await
+        }
+
+        async fn replace_image(&self, image: CampusImage) -> Result<(), StoreError> {
+            self.check()?;
+            self.inner.replace_image(image).await
+        }
+
+        async fn delete_image(&self, image_name: String) -> Result<(), StoreError> {
+            self.check()?;
+            self.inner.delete_image(image_name).await
+        }
+
+        async fn reload(&self, classroom_data: String, image_data: String) -> Result<(), StoreError> {
+            self.check()?;
+            self.inner.reload(classroom_data, image_data).await
+        }
+
+        async fn export(&self) -> Result<Dataset, StoreError> {
+            self.check()?;
+            self.exports.fetch_add(1, Ordering::SeqCst);
+            self.inner.export().await
+        }
+
+        async fn status(&self) -> Result<StoreStatus, StoreError> {
+            self.check()?;
+            self.inner.status().await
+        }
+    }
+
+    fn classroom(name: &str) -> ClassroomData {
+        serde_json::from_value(json!({"classroom": name, "description": "", "images": []})).unwrap()
+    }
+
+    async fn flaky_pair() -> (Arc<FlakyStore>, FallbackStore) {
+        let classroom_data = json!([{"classroom": "УК3 104", "description": "Крутая аудитория", "images": []}]).to_string();
+        let primary = Arc::new(FlakyStore{
+            inner: MemoryStore::new(classroom_data, "[]".to_owned()).unwrap(),
+            down: AtomicBool::new(false),
+            exports: AtomicU32::new(0)});
+        let backoff = Backoff{initial: Duration::from_millis(1), max: Duration::from_millis(4)};
+        let fallback = FallbackStore::new(primary.clone(), backoff, Duration::from_secs(300)).await.unwrap();
+        (primary, fallback)
+    }
+
+    #[actix_web::test]
+    async fn test_refresh_only_when_changed(){
+        let (primary, fallback) = flaky_pair().await;
+        assert_eq!(primary.exports.load(Ordering::SeqCst), 1);
+        fallback.check().await.unwrap();
+        assert_eq!(primary.exports.load(Ordering::SeqCst), 1);
+
+        primary.inner.insert_classroom(classroom("УК3 105")).await.unwrap();
+        fallback.check().await.unwrap();
+        assert_eq!(primary.exports.load(Ordering::SeqCst), 2);
+        assert!(fallback.snapshot().get_classroom("УК3 105".to_owned()).await.is_ok());
+
+        // Правка другой реплики не меняет число записей, но меняет счетчик правок
+        let mut edited = classroom("УК3 105");
+        edited.description = "Отредактировано".to_owned();
+        primary.inner.replace_classroom("УК3 105".to_owned(), edited).await.unwrap();
+        fallback.check().await.unwrap();
+        assert_eq!(primary.exports.load(Ordering::SeqCst), 3);
+        assert_eq!(fallback.snapshot().get_classroom("УК3 105".to_owned()).await.unwrap().description, "Отредактировано");
+
+        // Своя правка повторяется в копии, выгрузка не нужна
+        fallback.delete_classroom("УК3 105".to_owned()).await.unwrap();
+        fallback.check().await.unwrap();
+        assert_eq!(primary.exports.load(Ordering::SeqCst), 3);
+
+        let classroom_data = json!([{"classroom": "ГУК 305", "description": "", "images": []}]).to_string();
+        primary.inner.reload(classroom_data, "[]".to_owned()).await.unwrap();
+        fallback.check().await.unwrap();
+        assert_eq!(primary.exports.load(Ordering::SeqCst), 4);
+        assert!(fallback.snapshot().get_classroom("ГУК 305".to_owned()).await.is_ok());
+    }
+
+    #[test]
+    fn test_backoff(){
+        let backoff = Backoff::up_to(Duration::from_secs(30));
+        let delays: Vec<u64> = (0..7).map(|x|backoff.delay(x).as_secs()).collect();
+        assert_eq!(delays, vec![1, 2, 4, 8, 16, 30, 30]);
+        assert_eq!(backoff.delay(u32::MAX), Duration::from_secs(30));
+        assert_eq!(Backoff::up_to(Duration::from_millis(500)).delay(0), Duration::from_millis(500));
+    }
+
+    #[actix_web::test]
+    async fn test_connect_with_retry(){
+        let backoff = Backoff{initial: Duration::from_millis(1), max: Duration::from_millis(2)};
+        let attempts = AtomicU32::new(0);
+        let result = connect_with_retry(5, backoff, ||async{
+            match attempts.fetch_add(1, Ordering::SeqCst) {
+                0 | 1 => Err(StoreError::Unavailable("down".to_owned())),
+                _ => Ok("connected"),
+            }
+        }).await;
+        assert_eq!(result, Ok("connected"));
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+
+        attempts.store(0, Ordering::SeqCst);
+        let result: Result<(), StoreError> = connect_with_retry(2, backoff, ||async{
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err(StoreError::Unavailable("down".to_owned()))
+        }).await;
+        assert!(matches!(result, Err(StoreError::Unavailable(_))));
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+
+        attempts.store(0, Ordering::SeqCst);
+        let result: Result<(), StoreError> = connect_with_retry(5, backoff, ||async{
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err(StoreError::InvalidData("no password".to_owned()))
+        }).await;
+        assert!(matches!(result, Err(StoreError::InvalidData(_))));
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+    }
+
+    #[actix_web::test]
+    async fn test_fallback_and_recovery(){
+        let (primary, fallback) = flaky_pair().await;
+        fallback.insert_classroom(classroom("УК1 101")).await.unwrap();
+        assert!(fallback.degraded().is_none());
+
+        primary.down.store(true, Ordering::SeqCst);
+        let names = fallback.get_classroom_names().await.unwrap();
+        assert_eq!(names, vec!["УК3 104".to_owned(), "УК1 101".to_owned()]);
+        assert_eq!(fallback.degraded().unwrap().error, "connection refused");
+        assert!(matches!(fallback.get_classroom("УК5 505".to_owned()).await, Err(StoreError::ClassroomNotFound(_))));
+        assert!(matches!(fallback.delete_classroom("УК1 101".to_owned()).await, Err(StoreError::Unavailable(_))));
+        let status = fallback.status().await.unwrap();
+        assert_eq!(status.classrooms, 2);
+        assert!(status.degraded.is_some());
+        assert!(fallback.check().await.is_err());
+
+        primary.down.store(false, Ordering::SeqCst);
+        primary.inner.insert_classroom(classroom("ГУК 200")).await.unwrap();
+        fallback.check().await.unwrap();
+        assert!(fallback.degraded().is_none());
+        assert!(fallback.status().await.unwrap().degraded.is_none());
+
+        primary.down.store(true, Ordering::SeqCst);
+        assert!(fallback.get_classroom("ГУК 200".to_owned()).await.is_ok());
+    }
+
+    #[actix_web::test]
+    async fn test_monitor_recovers(){
+        let (primary, fallback) = flaky_pair().await;
+        let fallback = Arc::new(fallback);
+        let monitor = actix_web::rt::spawn({
+            let fallback = fallback.clone();
+            async move {fallback.run().await}
+        });
+        primary.down.store(true, Ordering::SeqCst);
+        assert!(fallback.get_classroom_list().await.is_ok());
+        assert!(fallback.degraded().is_some());
+
+        primary.down.store(false, Ordering::SeqCst);
+        for _ in 0..100 {
+            if fallback.degraded().is_none() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        assert!(fallback.degraded().is_none());
+        monitor.abort();
+    }
+
+    #[actix_web::test]
+    #[serial]
+    async fn test_readyz_degraded(){
+        let (primary, fallback) = flaky_pair().await;
+        let app = Server::builder()
+            .store(Arc::new(fallback))
+            .build()
+            .test_start()
+            .await
+            .unwrap();
+        primary.down.store(true, Ordering::SeqCst);
+
+        let res = app.call(TestRequest::with_uri("/readyz").to_request()).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        let res_body: serde_json::Value = actix_web::test::read_body_json(res).await;
+        assert_eq!(res_body["status"], "degraded");
+        assert_eq!(res_body["degraded"]["error"], "connection refused");
+
+        let res = app.call(TestRequest::with_uri("/classroomlist").to_request()).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+}
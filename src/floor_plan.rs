@@ -0,0 +1,226 @@
+use image::{ImageOutputFormat, Rgba, RgbaImage};
+use std::error::Error;
+use std::io::Cursor;
+use super::models::RoomPlan;
+
+/// Цвет отметки аудитории
+const HIGHLIGHT: Rgba<u8> = Rgba([220, 30, 30, 255]);
+/// Обводка метки, чтобы она была видна на темном плане
+const OUTLINE: Rgba<u8> = Rgba([255, 255, 255, 255]);
+/// Непрозрачность заливки контура и ореола вокруг метки
+const FILL_ALPHA: f32 = 0.35;
+/// Толщина контура аудитории в пикселях
+const LINE_WIDTH: i64 = 3;
+
+/// Рисует на плане этажа отметку аудитории
+///
+/// # Аргументы:
+/// - plan_image: план этажа (PNG или JPEG)
+/// - room: разметка аудитории на этом плане
+///
+/// # Что делает?
+/// - Одна точка: метка с белой обводкой и полупрозрачным ореолом
+/// - Две точки: прямоугольник по противоположным углам
+/// - Три и больше: многоугольник с полупрозрачной заливкой
+///
+/// # Примечание:
+/// Выдает PNG. Выдает ошибку, если разметка пустая или выходит за пределы плана
+pub fn highlight_room(plan_image: &[u8], room: &RoomPlan) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut canvas = image::load_from_memory(plan_image)?.to_rgba8();
+    check_points(room, canvas.dimensions())?;
+
+    let points: Vec<(i64, i64)> = room.points.iter().map(|[x, y]|(*x as i64, *y as i64)).collect();
+    match points.as_slice() {
+        [center] => draw_marker(&mut canvas, *center),
+        [(x1, y1), (x2, y2)] => {
+            let corners = [(*x1, *y1), (*x2, *y1), (*x2, *y2), (*x1, *y2)];
+            draw_polygon(&mut canvas, &corners);
+        },
+        polygon => draw_polygon(&mut canvas, polygon),
+    }
+
+    let mut result = Vec::new();
+    canvas.write_to(&mut Cursor::new(&mut result), ImageOutputFormat::Png)?;
+    Ok(result)
+}
+
+/// Выдает ширину и высоту плана этажа по заголовку картинки, не раскодируя ее целиком
+pub fn plan_size(plan_image: &[u8]) -> Result<(u32, u32), Box<dyn Error>> {
+    Ok(image::io::Reader::new(Cursor::new(plan_image)).with_guessed_format()?.into_dimensions()?)
+}
+
+/// Проверяет, что разметка аудитории не пустая и лежит в пределах плана
+///
+/// # Аргументы:
+/// - room: разметка аудитории
+/// - (width, height): размер плана этажа, см. plan_size
+pub fn check_points(room: &RoomPlan, (width, height): (u32, u32)) -> Result<(), String> {
+    if room.points.is_empty() {
+        return Err("Room plan has no points".to_owned());
+    }
+    if let Some([x, y]) = room.points.iter().find(|[x, y]|*x >= width || *y >= height) {
+        return Err(format!("Point [{}, {}] is outside the {}x{} plan", x, y, width, height));
+    }
+    Ok(())
+}
+
+/// Радиус метки: заметный и на маленьком, и на большом плане
+fn marker_radius(canvas: &RgbaImage) -> i64 {
+    let (width, height) = canvas.dimensions();
+    (width.min(height) as i64 / 40).max(6)
+}
+
+fn draw_marker(canvas: &mut RgbaImage, (cx, cy): (i64, i64)) {
+    let radius = marker_radius(canvas);
+    let halo = radius * 3;
+    for y in cy - halo..=cy + halo {
+        for x in cx - halo..=cx + halo {
+            let distance = (((x - cx).pow(2) + (y - cy).pow(2)) as f64).sqrt() as i64;
+            if distance <= radius {
+                put(canvas, x, y, HIGHLIGHT);
+            } else if distance <= radius + 2 {
+                put(canvas, x, y, OUTLINE);
+            } else if distance <= halo {
+                blend(canvas, x, y, HIGHLIGHT, FILL_ALPHA);
+            }
+        }
+    }
+}
+
+fn draw_polygon(canvas: &mut RgbaImage, polygon: &[(i64, i64)]) {
+    let min_x = polygon.iter().map(|p|p.0).min().unwrap_or_default();
+    let max_x = polygon.iter().map(|p|p.0).max().unwrap_or_default();
+    let min_y = polygon.iter().map(|p|p.1).min().unwrap_or_default();
+    let max_y = polygon.iter().map(|p|p.1).max().unwrap_or_default();
+    for y in min_y..=max_y {
+        for x in min_x..=max_x {
+            if contains(polygon, x as f64 + 0.5, y as f64 + 0.5) {
+                blend(canvas, x, y, HIGHLIGHT, FILL_ALPHA);
+            }
+        }
+    }
+    for (i, start) in polygon.iter().enumerate() {
+        let end = polygon[(i + 1) % polygon.len()];
+        draw_line(canvas, *start, end);
+    }
+}
+
+/// Проверка точки внутри многоугольника по правилу четности пересечений
+fn contains(polygon: &[(i64, i64)], x: f64, y: f64) -> bool {
+    let mut inside = false;
+    for (i, &(x1, y1)) in polygon.iter().enumerate() {
+        let (x2, y2) = polygon[(i + 1) % polygon.len()];
+        let (x1, y1, x2, y2) = (x1 as f64, y1 as f64, x2 as f64, y2 as f64);
+        if (y1 > y) != (y2 > y) && x < x1 + (y - y1) * (x2 - x1) / (y2 - y1) {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+/// Отрезок по алгоритму Брезенхэма квадратной кистью толщиной LINE_WIDTH
+fn draw_line(canvas: &mut RgbaImage, (x0, y0): (i64, i64), (x1, y1): (i64, i64)) {
+    let (dx, dy) = ((x1 - x0).abs(), -(y1 - y0).abs());
+    let (sx, sy) = (if x0 < x1 {1} else {-1}, if y0 < y1 {1} else {-1});
+    let (mut x, mut y, mut error) = (x0, y0, dx + dy);
+    loop {
+        for by in -LINE_WIDTH / 2..=LINE_WIDTH / 2 {
+            for bx in -LINE_WIDTH / 2..=LINE_WIDTH / 2 {
+                put(canvas, x + bx, y + by, HIGHLIGHT);
+            }
+        }
+        if x == x1 && y == y1 {
+            break;
+        }
+        let doubled = 2 * error;
+        if doubled >= dy {
+            error += dy;
+            x += sx;
+        }
+        if doubled <= dx {
+            error += dx;
+            y += sy;
+        }
+    }
+}
+
+fn put(canvas: &mut RgbaImage, x: i64, y: i64, color: Rgba<u8>) {
+    if x >= 0 && y >= 0 && (x as u32) < canvas.width() && (y as u32) < canvas.height() {
+        canvas.put_pixel(x as u32, y as u32, color);
+    }
+}
+
+fn blend(canvas: &mut RgbaImage, x: i64, y: i64, color: Rgba<u8>, alpha: f32) {
+    if x < 0 || y < 0 || x as u32 >= canvas.width() || y as u32 >= canvas.height() {
+        return;
+    }
+    let pixel = canvas.get_pixel_mut(x as u32, y as u32);
+    for channel in 0..3 {
+        let mixed = pixel.0[channel] as f32 * (1.0 - alpha) + color.0[channel] as f32 * alpha;
+        pixel.0[channel] = mixed.round() as u8;
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    /// Белый план этажа 200x100 в PNG
+    fn blank_plan() -> Vec<u8> {
+        let canvas = RgbaImage::from_pixel(200, 100, Rgba([255, 255, 255, 255]));
+        let mut bytes = Vec::new();
+        canvas.write_to(&mut Cursor::new(&mut bytes), ImageOutputFormat::Png).unwrap();
+        bytes
+    }
+
+    fn room(points: &[[u32; 2]]) -> RoomPlan {
+        RoomPlan{map: None, points: points.to_vec()}
+    }
+
+    fn render(points: &[[u32; 2]]) -> RgbaImage {
+        let result = highlight_room(&blank_plan(), &room(points)).unwrap();
+        image::load_from_memory(&result).unwrap().to_rgba8()
+    }
+
+    #[test]
+    fn test_marker(){
+        let canvas = render(&[[50, 50]]);
+        assert_eq!(*canvas.get_pixel(50, 50), HIGHLIGHT);
+        assert_eq!(*canvas.get_pixel(58, 50), OUTLINE);
+        assert_ne!(*canvas.get_pixel(65, 50), Rgba([255, 255, 255, 255]));
+        assert_eq!(*canvas.get_pixel(150, 50), Rgba([255, 255, 255, 255]));
+    }
+
+    #[test]
+    fn test_rectangle(){
+        let canvas = render(&[[100, 20], [180, 80]]);
+        assert_eq!(*canvas.get_pixel(100, 50), HIGHLIGHT);
+        let inside = canvas.get_pixel(140, 50);
+        assert!(inside.0[0] > inside.0[1] && inside.0[1] < 255);
+        assert_eq!(*canvas.get_pixel(50, 50), Rgba([255, 255, 255, 255]));
+    }
+
+    #[test]
+    fn test_polygon(){
+        let canvas = render(&[[10, 10], [90, 10], [10, 90]]);
+        assert_ne!(*canvas.get_pixel(30, 30), Rgba([255, 255, 255, 255]));
+        assert_eq!(*canvas.get_pixel(80, 80), Rgba([255, 255, 255, 255]));
+    }
+
+    #[test]
+    fn test_bad_plan(){
+        assert!(highlight_room(&blank_plan(), &room(&[])).is_err());
+        assert!(highlight_room(&blank_plan(), &room(&[[200, 10]])).is_err());
+        assert!(highlight_room(b"not an image", &room(&[[10, 10]])).is_err());
+    }
+
+    #[test]
+    fn test_plan_bounds(){
+        let size = plan_size(&blank_plan()).unwrap();
+        assert_eq!(size, (200, 100));
+        assert!(check_points(&room(&[[199, 99]]), size).is_ok());
+        assert!(check_points(&room(&[[10, 10], [10, 100]]), size).is_err());
+        assert!(check_points(&room(&[]), size).is_err());
+        assert!(plan_size(b"not an image").is_err());
+    }
+}
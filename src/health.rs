@@ -0,0 +1,124 @@
+use actix_web::http::StatusCode;
+use actix_web::{get, HttpResponse, web};
+use serde_json::json;
+use super::error::ApiError;
+use super::server::SharedStore;
+use super::store::StoreStatus;
+
+/// Хэш коммита, из которого собран сервис, см. build.rs
+const GIT_HASH: &str = env!("GIT_HASH");
+
+/// Ответ /readyz
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct Readiness{
+    status: String,
+    #[serde(flatten)]
+    store: StoreStatus,
+}
+
+/// Ответ /version
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct VersionInfo{
+    version: String,
+    git_hash: String,
+    /// Контрольная сумма набора данных, null если хранилище недоступно
+    dataset_checksum: Option<String>,
+}
+
+/// Регистрирует обработчики для проверок оркестратора
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(healthz)
+        .service(readyz)
+        .service(version);
+}
+
+/// Процесс жив и отвечает на запросы, хранилище не проверяется
+#[get("/healthz")]
+async fn healthz() -> HttpResponse {
+    HttpResponse::Ok().json(json!({"status": "ok"}))
+}
+
+/// Сервис готов отвечать: хранилище доступно и в нем есть аудитории
+///
+/// # Примечание:
+/// 503 db_unavailable, если база не отвечает на ping, и 503 not_ready, если аудиторий нет.
+/// Пока база недоступна, а данные отдаются из копии в памяти, выдает 200 со status "degraded"
+/// и причиной в поле degraded
+#[get("/readyz")]
+async fn readyz(db_client: SharedStore) -> Result<HttpResponse, ApiError> {
+    let status = db_client.status().await?;
+    if status.classrooms == 0 {
+        return Err(ApiError::new(StatusCode::SERVICE_UNAVAILABLE, "not_ready", "No classrooms loaded")
+            .with_details(json!(status)));
+    }
+    let readiness = if status.degraded.is_some() {"degraded"} else {"ready"};
+    Ok(HttpResponse::Ok().json(Readiness{status: readiness.to_owned(), store: status}))
+}
+
+#[get("/version")]
+async fn version(db_client: SharedStore) -> HttpResponse {
+    let dataset_checksum = match db_client.status().await {
+        Ok(status) => status.dataset.map(|x|x.checksum),
+        Err(_) => None,
+    };
+    HttpResponse::Ok().json(VersionInfo{
+        version: env!("CARGO_PKG_VERSION").to_owned(),
+        git_hash: GIT_HASH.to_owned(),
+        dataset_checksum})
+}
+
+#[cfg(test)]
+mod tests{
+    use serde_json::json;
+    use serial_test::serial;
+    use actix_service::Service;
+    use actix_web::http::StatusCode;
+    use actix_web::test::TestRequest;
+    use super::super::seed::dataset_checksum;
+    use super::super::server::{Server, StorageKind};
+
+    #[actix_web::test]
+    #[serial]
+    async fn test_health(){
+        let classroom_data = json!([{"classroom": "УК3 104", "description": "", "images": []}]).to_string();
+        let app = Server::builder()
+            .classroom_data(classroom_data.to_owned())
+            .storage(StorageKind::Memory)
+            .build()
+            .test_start()
+            .await
+            .unwrap();
+
+        let res = app.call(TestRequest::with_uri("/healthz").to_request()).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let res = app.call(TestRequest::with_uri("/readyz").to_request()).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        let res_body: serde_json::Value = actix_web::test::read_body_json(res).await;
+        assert_eq!(res_body["status"], "ready");
+        assert_eq!((res_body["classrooms"].as_u64(), res_body["images"].as_u64()), (Some(1), Some(0)));
+
+        let res = app.call(TestRequest::with_uri("/version").to_request()).await.unwrap();
+        let res_body: serde_json::Value = actix_web::test::read_body_json(res).await;
+        assert_eq!(res_body["version"], env!("CARGO_PKG_VERSION"));
+        assert!(!res_body["git_hash"].as_str().unwrap().is_empty());
+        assert_eq!(res_body["dataset_checksum"], dataset_checksum(&classroom_data, "[]"));
+    }
+
+    #[actix_web::test]
+    #[serial]
+    async fn test_not_ready(){
+        let app = Server::builder()
+            .storage(StorageKind::Memory)
+            .build()
+            .test_start()
+            .await
+            .unwrap();
+        let res = app.call(TestRequest::with_uri("/readyz").to_request()).await.unwrap();
+        assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let res_body: serde_json::Value = actix_web::test::read_body_json(res).await;
+        assert_eq!(res_body["code"], "not_ready");
+        let res = app.call(TestRequest::with_uri("/healthz").to_request()).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+}
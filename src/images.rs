@@ -0,0 +1,151 @@
+use base64::Engine;
+use sha2::{Digest, Sha256};
+use std::borrow::Cow;
+use std::error::Error;
+use super::error::StoreError;
+use super::models::CampusImage;
+
+/// Картинка корпуса, раскодированная из Base64 для отдачи в бинарном виде
+#[derive(Debug, Clone)]
+pub struct ImageResource{
+    pub bytes: Vec<u8>,
+    pub content_type: &'static str,
+    /// Сильный ETag по SHA-256 содержимого, уже в кавычках
+    pub etag: String,
+}
+
+impl ImageResource{
+    /// Раскодирует картинку из хранилища
+    ///
+    /// # Аргументы:
+    /// - image: картинка с закодированным в Base64 содержимым
+    ///
+    /// # Примечание:
+    /// Префикс вида "data:image/png;base64," допускается и отбрасывается
+    pub fn decode(image: &CampusImage) -> Result<Self, Box<dyn Error>> {
+        let bytes = decode_base64(&image.value)?;
+        let content_type = content_type(&image.name, &bytes);
+        let etag = format!("\"{}\"", content_hash(&bytes));
+        Ok(Self{bytes, content_type, etag})
+    }
+}
+
+/// Раскодирует содержимое картинки из Base64, допуская префикс "data:image/png;base64,"
+///
+/// # Примечание:
+/// Пробелы и переводы строк внутри пропускаются, так что Base64 с переносами (MIME) тоже читается
+pub fn decode_base64(value: &str) -> Result<Vec<u8>, base64::DecodeError> {
+    let value = match value.split_once(";base64,") {
+        Some((_, data)) => data,
+        None => value,
+    };
+    let value = match value.contains(|c: char|c.is_ascii_whitespace()) {
+        true => Cow::Owned(value.chars().filter(|c|!c.is_ascii_whitespace()).collect::<String>()),
+        false => Cow::Borrowed(value),
+    };
+    base64::engine::general_purpose::STANDARD.decode(value.as_bytes())
+}
+
+/// Раскодирует картинку перед сохранением в хранилище
+///
+/// # Примечание:
+/// Выдает ошибку StoreError::InvalidData с названием картинки, если она не в Base64
+pub fn decode_image(image: &CampusImage) -> Result<Vec<u8>, StoreError> {
+    decode_base64(&image.value)
+        .map_err(|e|StoreError::InvalidData(format!("image {} is not valid Base64: {}", image.name, e)))
+}
+
+/// Кодирует содержимое картинки в Base64, как его отдает API
+pub fn encode_base64(bytes: &[u8]) -> String {
+    base64::engine::general_purpose::STANDARD.encode(bytes)
+}
+
+/// Адрес содержимого картинки: SHA-256 в hex. Одинаковые картинки под разными названиями
+/// имеют один адрес и хранятся один раз
+pub fn content_hash(bytes: &[u8]) -> String {
+    hex::encode(Sha256::digest(bytes))
+}
+
+/// Определяет тип картинки по сигнатуре, а если она незнакома - по расширению имени
+fn content_type(name: &str, bytes: &[u8]) -> &'static str {
+    if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        return "image/png";
+    }
+    if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        return "image/jpeg";
+    }
+    if bytes.starts_with(b"GIF8") {
+        return "image/gif";
+    }
+    if bytes.len() >= 12 && bytes.starts_with(b"RIFF") && &bytes[8..12] == b"WEBP" {
+        return "image/webp";
+    }
+    let extension = name.rsplit_once('.').map(|(_, ext)|ext.to_lowercase()).unwrap_or_default();
+    match extension.as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        _ => "application/octet-stream",
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn image(name: &str, bytes: &[u8]) -> CampusImage {
+        CampusImage{
+            name: name.to_owned(),
+            value: base64::engine::general_purpose::STANDARD.encode(bytes),
+        }
+    }
+
+    #[test]
+    fn test_decode_png(){
+        let resource = ImageResource::decode(&image("UK3-left.jpg", b"\x89PNG\r\n\x1a\nrest")).unwrap();
+        assert_eq!(resource.content_type, "image/png");
+        assert_eq!(resource.bytes, b"\x89PNG\r\n\x1a\nrest");
+        assert!(resource.etag.starts_with('"') && resource.etag.ends_with('"'));
+    }
+
+    #[test]
+    fn test_decode_by_extension(){
+        assert_eq!(ImageResource::decode(&image("a.JPG", b"data")).unwrap().content_type, "image/jpeg");
+        assert_eq!(ImageResource::decode(&image("a", b"data")).unwrap().content_type, "application/octet-stream");
+    }
+
+    #[test]
+    fn test_decode_data_uri(){
+        let mut data = image("a.png", b"\xFF\xD8\xFFjpeg");
+        data.value = format!("data:image/jpeg;base64,{}", data.value);
+        assert_eq!(ImageResource::decode(&data).unwrap().content_type, "image/jpeg");
+    }
+
+    #[test]
+    fn test_content_hash(){
+        let resource = ImageResource::decode(&image("a.png", b"data")).unwrap();
+        assert_eq!(resource.etag, format!("\"{}\"", content_hash(b"data")));
+        assert_eq!(content_hash(b"data").len(), 64);
+        assert_eq!(decode_base64(&encode_base64(b"data")).unwrap(), b"data");
+    }
+
+    #[test]
+    fn test_decode_wrapped(){
+        let bytes: Vec<u8> = (0..=255).collect();
+        let encoded = encode_base64(&bytes);
+        let wrapped = encoded.as_bytes()
+            .chunks(76)
+            .map(|line|std::str::from_utf8(line).unwrap())
+            .collect::<Vec<_>>()
+            .join("\r\n");
+        assert_eq!(decode_base64(&format!(" {}\n", wrapped)).unwrap(), bytes);
+        assert_eq!(decode_base64(&format!("data:image/png;base64,{}", wrapped)).unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_decode_bad(){
+        let data = CampusImage{name: "a.png".to_owned(), value: "bibabob!".to_owned()};
+        assert!(ImageResource::decode(&data).is_err());
+    }
+}
@@ -1,2 +1,26 @@
+pub mod admin;
+pub mod api;
+pub mod blob_store;
+pub mod cache;
+pub mod config;
+pub mod error;
+pub mod fallback;
+pub mod floor_plan;
+pub mod health;
+pub mod images;
+pub mod location;
+pub mod maps;
+pub mod memory_store;
+pub mod metrics;
+pub mod models;
 pub mod mongo_client;
+pub mod negotiate;
+pub mod reload;
+pub mod route;
+pub mod search;
+pub mod seed;
 pub mod server;
+pub mod store;
+pub mod telemetry;
+pub mod thumbnails;
+pub mod validate;
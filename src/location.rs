@@ -0,0 +1,210 @@
+/// Тип помещения, выведенный из имени аудитории
+#[derive(Debug, serde::Serialize, serde::Deserialize, utoipa::ToSchema, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ClassroomKind{
+    /// Обычная аудитория в учебном корпусе ("УК3 104")
+    Classroom,
+    /// Лекционная (поточная) аудитория: "лекц." или "поточн." в имени ("ГУК 101 поточн.")
+    LectureHall,
+    /// Лаборатория: "лаб." в имени ("УК1 215 лаб.")
+    Lab,
+    /// Помещение кафедры ("_Каф. БУ", "Кафедра ТМН")
+    DepartmentOffice,
+    /// Мастерская ("Ск. маст.")
+    Workshop,
+    /// Дистанционное занятие, физического помещения нет ("Дист.")
+    Remote,
+    /// Имя не удалось разобрать ("УТК")
+    #[default]
+    Unknown,
+}
+
+impl ClassroomKind{
+    /// Есть ли у помещения корпус и номер, до которого можно построить маршрут
+    pub fn is_room(self) -> bool {
+        matches!(self, Self::Classroom | Self::LectureHall | Self::Lab)
+    }
+
+    /// Тип по отдельному слову-пометке в имени: "лаб.", "лекц.", "поточн."
+    fn from_marker(word: &str) -> Option<Self> {
+        let word = word.to_lowercase();
+        if word.starts_with("лаб") {
+            return Some(Self::Lab);
+        }
+        if word.starts_with("лекц") || word.starts_with("поточ") {
+            return Some(Self::LectureHall);
+        }
+        None
+    }
+}
+
+/// Разобранное местоположение аудитории
+///
+/// # Что делает?
+/// Раскладывает строку вида "УК3 104" на корпус, этаж, номер и суффикс,
+/// чтобы бот мог сказать "3 этаж УК3", а не повторять сырое имя
+#[derive(Debug, serde::Serialize, serde::Deserialize, utoipa::ToSchema, Clone, PartialEq, Eq, Default)]
+pub struct ClassroomLocation{
+    /// Корпус: "ГУК", "УК3", "КБ"...
+    pub building: Option<String>,
+    /// Этаж, если его можно вывести из номера (0 - цокольный)
+    pub floor: Option<u32>,
+    /// Номер аудитории без суффикса: "104", "021"
+    pub room: Option<String>,
+    /// Крыло/литера перед номером: "А" в "УК5 А3"
+    pub wing: Option<String>,
+    /// Суффикс после номера: "а" в "ГУК 412а", "(1)" в "УК3 412(1)"
+    pub suffix: Option<String>,
+    pub kind: ClassroomKind,
+}
+
+impl ClassroomLocation{
+    /// Разбирает имя аудитории
+    ///
+    /// # Аргументы:
+    /// - name: имя аудитории из classrooms.json
+    ///
+    /// # Примечание:
+    /// Не выдает ошибок: нераспознанные имена получают тип Unknown.
+    /// Лекционные и лаборатории отличаются от обычных аудиторий только пометкой в имени:
+    /// в номерах БГТУ этого нет, и без пометки аудитория считается Classroom
+    pub fn parse(name: &str) -> Self {
+        let name = name.trim();
+        let lowercase = name.to_lowercase();
+        let plain = lowercase.trim_start_matches('_');
+
+        if plain.starts_with("каф") || plain.contains(" каф") {
+            return Self::of_kind(ClassroomKind::DepartmentOffice);
+        }
+        if plain.starts_with("дист") {
+            return Self::of_kind(ClassroomKind::Remote);
+        }
+        if plain.contains("маст") {
+            return Self::of_kind(ClassroomKind::Workshop);
+        }
+
+        let marked = name.split_whitespace().find_map(ClassroomKind::from_marker);
+        let name = name
+            .split_whitespace()
+            .filter(|word|ClassroomKind::from_marker(word).is_none())
+            .collect::<Vec<_>>()
+            .join(" ");
+        let (building, rest) = match name.split_once(char::is_whitespace) {
+            Some(parts) => parts,
+            None => return Self::of_kind(marked.unwrap_or_default()),
+        };
+        let rest: String = rest.chars().filter(|c|!c.is_whitespace()).collect();
+
+        let digits_start = match rest.find(|c: char|c.is_ascii_digit()) {
+            Some(idx) => idx,
+            None => return Self::of_kind(marked.unwrap_or_default()),
+        };
+        let digits_end = rest[digits_start..]
+            .find(|c: char|!c.is_ascii_digit())
+            .map(|idx|digits_start + idx)
+            .unwrap_or(rest.len());
+        let (wing, room, suffix) = (&rest[..digits_start], &rest[digits_start..digits_end], &rest[digits_end..]);
+
+        Self{
+            building: Some(building.to_owned()),
+            floor: Self::floor_of(room),
+            room: Some(room.to_owned()),
+            wing: Some(wing.to_owned()).filter(|x|!x.is_empty()),
+            suffix: Some(suffix.to_owned()).filter(|x|!x.is_empty()),
+            kind: marked.unwrap_or(ClassroomKind::Classroom),
+        }
+    }
+
+    /// Выводит этаж из номера аудитории
+    ///
+    /// # Примечание:
+    /// "104" - 1 этаж, "021"/"04" - цокольный, по "6" или "12" этаж не понять
+    fn floor_of(room: &str) -> Option<u32> {
+        if room.len() >= 3 {
+            return room[..room.len() - 2].parse().ok();
+        }
+        if room.len() == 2 && room.starts_with('0') {
+            return Some(0);
+        }
+        None
+    }
+
+    fn of_kind(kind: ClassroomKind) -> Self {
+        Self{kind, ..Default::default()}
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn test_parse_plain_room(){
+        let location = ClassroomLocation::parse("УК3 104");
+        assert_eq!(location, ClassroomLocation{
+            building: Some("УК3".to_owned()),
+            floor: Some(1),
+            room: Some("104".to_owned()),
+            wing: None,
+            suffix: None,
+            kind: ClassroomKind::Classroom,
+        });
+    }
+
+    #[test]
+    fn test_parse_basement_and_suffixes(){
+        let location = ClassroomLocation::parse("ГУК 021А");
+        assert_eq!(location.floor, Some(0));
+        assert_eq!(location.room.as_deref(), Some("021"));
+        assert_eq!(location.suffix.as_deref(), Some("А"));
+
+        let location = ClassroomLocation::parse("УК4 003 А");
+        assert_eq!(location.room.as_deref(), Some("003"));
+        assert_eq!(location.suffix.as_deref(), Some("А"));
+
+        let location = ClassroomLocation::parse("УК3 412(1)");
+        assert_eq!(location.floor, Some(4));
+        assert_eq!(location.suffix.as_deref(), Some("(1)"));
+
+        assert_eq!(ClassroomLocation::parse("УК3 04").floor, Some(0));
+    }
+
+    #[test]
+    fn test_parse_wing_and_unknown_floor(){
+        let location = ClassroomLocation::parse("УК5 А3");
+        assert_eq!(location.building.as_deref(), Some("УК5"));
+        assert_eq!(location.wing.as_deref(), Some("А"));
+        assert_eq!(location.room.as_deref(), Some("3"));
+        assert_eq!(location.floor, None);
+
+        assert_eq!(ClassroomLocation::parse("УК7 12").floor, None);
+    }
+
+    #[test]
+    fn test_parse_special_names(){
+        for name in ["_Каф. БУ", "_каф.", "Кафедра", "кафедра", "Кафедра ТМН", "Каф. физ. восп.", "Баз. каф.", "_Каф СУ"] {
+            assert_eq!(ClassroomLocation::parse(name).kind, ClassroomKind::DepartmentOffice, "{name}");
+        }
+        assert_eq!(ClassroomLocation::parse("Дист.").kind, ClassroomKind::Remote);
+        assert_eq!(ClassroomLocation::parse("Ск. маст.").kind, ClassroomKind::Workshop);
+
+        let location = ClassroomLocation::parse("УТК");
+        assert_eq!(location.kind, ClassroomKind::Unknown);
+        assert_eq!(location.building, None);
+    }
+
+    #[test]
+    fn test_parse_lecture_hall_and_lab(){
+        let location = ClassroomLocation::parse("УК1 215 лаб.");
+        assert_eq!(location.kind, ClassroomKind::Lab);
+        assert_eq!(location.room.as_deref(), Some("215"));
+        assert_eq!(location.suffix, None);
+
+        let location = ClassroomLocation::parse("ГУК 101а Поточн.");
+        assert_eq!(location.kind, ClassroomKind::LectureHall);
+        assert_eq!(location.suffix.as_deref(), Some("а"));
+
+        assert_eq!(ClassroomLocation::parse("Лаб. химии").kind, ClassroomKind::Lab);
+        assert_eq!(ClassroomLocation::parse("УК3 104").kind, ClassroomKind::Classroom);
+    }
+}
@@ -1,22 +1,64 @@
-use actix_navigation_service::server::Server;
-use tokio;
+use std::error::Error;
+use actix_navigation_service::config::Config;
+use actix_navigation_service::telemetry::init_logging;
+use actix_navigation_service::validate::{validate_dataset, Severity};
 
 fn main() {
-    let classroom_data = std::fs::read_to_string("classrooms.json").expect("No classrooms.json");
-    let image_data = std::fs::read_to_string("images.json").expect("No images.json");
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("validate") {
+        std::process::exit(validate(&args[1..]));
+    }
+    if let Err(e) = run() {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+/// Читает настройки и запускает сервер, любая ошибка настройки завершает процесс
+fn run() -> Result<(), Box<dyn Error>> {
+    let config = Config::load()?;
+    init_logging(config.log.format, &config.log.level).map_err(|e|format!("log.level: {}", e))?;
+    let builder = config.server_builder()?;
     tokio::runtime::Builder::new_multi_thread()
         .enable_all()
-        .build()
-        .unwrap()
-        .block_on(async {
-            Server::builder()
-                .image_data(image_data)
-                .classroom_data(classroom_data)
-                .host("0.0.0.0".to_owned())
-                .port(8080)
-                .build()
-                .start()
-                .await
-                .unwrap();
-        })
+        .build()?
+        .block_on(builder.build().start())
+}
+
+/// Подкоманда validate: проверяет classrooms.json и images.json перед выкладкой
+///
+/// # Аргументы:
+/// - args: [--strict] [classrooms.json] [images.json] [maps.json]
+///
+/// # Примечание:
+/// Выдает код выхода 1 при ошибках, а с --strict - и при предупреждениях.
+/// maps.json по умолчанию необязателен: без него разметка аудиторий не сверяется с планами этажей
+fn validate(args: &[String]) -> i32 {
+    let strict = args.iter().any(|x|x == "--strict");
+    let mut files = args.iter().filter(|x|!x.starts_with("--"));
+    let classroom_path = files.next().map(String::as_str).unwrap_or("classrooms.json");
+    let image_path = files.next().map(String::as_str).unwrap_or("images.json");
+    let map_path = files.next().map(String::as_str);
+    let map_data = match map_path {
+        Some(path) => read_file(path).map(Some),
+        None => Ok(std::fs::read_to_string("maps.json").ok()),
+    };
+    let report = read_file(classroom_path)
+        .and_then(|classrooms|Ok((classrooms, read_file(image_path)?, map_data?)))
+        .and_then(|(classrooms, images, maps)|validate_dataset(&classrooms, &images, maps.as_deref()));
+    match report {
+        Ok(report) => {
+            println!("{}", report);
+            let failed = report.has_errors() || (strict && report.count(Severity::Warning) > 0);
+            i32::from(failed)
+        },
+        Err(e) => {
+            eprintln!("error: {}", e);
+            1
+        },
+    }
+}
+
+fn read_file(path: &str) -> Result<String, Box<dyn Error>> {
+    std::fs::read_to_string(path).map_err(|e|format!("{}: {}", path, e).into())
 }
@@ -0,0 +1,162 @@
+use std::collections::HashSet;
+use std::error::Error;
+use super::location::ClassroomLocation;
+use super::models::RoomPlan;
+
+/// Что показывает карта
+#[derive(Debug, serde::Serialize, serde::Deserialize, utoipa::ToSchema, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MapKind{
+    /// Весь кампус
+    Campus,
+    /// Один корпус
+    Building,
+    /// Один этаж корпуса
+    Floor,
+}
+
+/// Карта кампуса из maps.json
+#[derive(Debug, serde::Serialize, serde::Deserialize, utoipa::ToSchema, Clone, PartialEq)]
+pub struct CampusMap{
+    /// Идентификатор для /maps/{id}: "campus", "guk", "uk3-2"
+    pub id: String,
+    pub title: String,
+    pub kind: MapKind,
+    #[serde(default)]
+    pub building: Option<String>,
+    #[serde(default)]
+    pub floor: Option<u32>,
+    /// Название картинки (CampusImage) с картой
+    pub image: String,
+}
+
+/// Список карт кампуса
+///
+/// # Что делает?
+/// - Загружается из maps.json
+/// - Выдает карту по id и общую карту кампуса для /map
+#[derive(Debug, Clone)]
+pub struct MapCatalog{
+    maps: Vec<CampusMap>,
+}
+
+impl MapCatalog{
+    /// Загружает карты из JSON-строки
+    ///
+    /// # Аргументы:
+    /// - data: карты в виде [CampusMap]
+    ///
+    /// # Примечание:
+    /// Выдает ошибку на повторяющиеся id, на карты этажей без этажа и если нет ни одной карты кампуса
+    pub fn from_json(data: &str) -> Result<Self, Box<dyn Error>> {
+        let maps: Vec<CampusMap> = serde_json::from_str(data)?;
+        let mut ids = HashSet::new();
+        for map in &maps {
+            if !ids.insert(map.id.as_str()) {
+                return Err(format!("Duplicate map id: {}", map.id).into());
+            }
+            if map.kind != MapKind::Campus && map.building.is_none() {
+                return Err(format!("Map {} has no building", map.id).into());
+            }
+            if map.kind == MapKind::Floor && map.floor.is_none() {
+                return Err(format!("Floor map {} has no floor", map.id).into());
+            }
+        }
+        if !maps.iter().any(|map|map.kind == MapKind::Campus) {
+            return Err("No campus map".into());
+        }
+        Ok(Self{maps})
+    }
+
+    /// Все карты в порядке из maps.json
+    pub fn maps(&self) -> &[CampusMap] {
+        &self.maps
+    }
+
+    /// Карта по id
+    pub fn get(&self, id: &str) -> Option<&CampusMap> {
+        self.maps.iter().find(|map|map.id == id)
+    }
+
+    /// План этажа корпуса
+    ///
+    /// # Аргументы:
+    /// - building: корпус, как в ClassroomLocation ("УК3", "ГУК")
+    /// - floor: этаж
+    pub fn floor_plan(&self, building: &str, floor: u32) -> Option<&CampusMap> {
+        self.maps.iter().find(|map|{
+            map.kind == MapKind::Floor && map.building.as_deref() == Some(building) && map.floor == Some(floor)
+        })
+    }
+
+    /// План этажа, на котором размечена аудитория
+    ///
+    /// # Аргументы:
+    /// - room: разметка аудитории, карта из нее важнее корпуса и этажа
+    /// - location: разобранное имя аудитории
+    pub fn room_plan(&self, room: &RoomPlan, location: &ClassroomLocation) -> Option<&CampusMap> {
+        match (&room.map, &location.building, location.floor) {
+            (Some(map_id), _, _) => self.get(map_id),
+            (None, Some(building), Some(floor)) => self.floor_plan(building, floor),
+            _ => None,
+        }
+    }
+
+    /// Общая карта кампуса: первая карта типа campus
+    pub fn general(&self) -> &CampusMap {
+        self.maps.iter()
+            .find(|map|map.kind == MapKind::Campus)
+            .expect("MapCatalog always has a campus map")
+    }
+}
+
+impl Default for MapCatalog{
+    /// Единственная карта кампуса general_map.png, как до появления maps.json
+    fn default() -> Self {
+        Self{maps: vec![CampusMap{
+            id: "campus".to_owned(),
+            title: "Карта кампуса БГТУ".to_owned(),
+            kind: MapKind::Campus,
+            building: None,
+            floor: None,
+            image: "general_map.png".to_owned()}]}
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use serde_json::json;
+    use super::*;
+
+    #[test]
+    fn test_catalog_ok(){
+        let data = json!([
+            {"id": "uk3", "title": "УК3", "kind": "building", "building": "УК3", "image": "uk3.png"},
+            {"id": "campus", "title": "Кампус", "kind": "campus", "image": "campus.png"},
+            {"id": "uk3-2", "title": "УК3, 2 этаж", "kind": "floor", "building": "УК3", "floor": 2, "image": "uk3-2.png"},
+        ]).to_string();
+        let catalog = MapCatalog::from_json(&data).unwrap();
+        assert_eq!(catalog.maps().len(), 3);
+        assert_eq!(catalog.general().image, "campus.png");
+        assert_eq!(catalog.get("uk3-2").unwrap().floor, Some(2));
+        assert_eq!(catalog.floor_plan("УК3", 2).unwrap().id, "uk3-2");
+        assert!(catalog.floor_plan("УК3", 3).is_none());
+        assert!(catalog.get("uk4").is_none());
+    }
+
+    #[test]
+    fn test_catalog_bad(){
+        let campus = json!({"id": "campus", "title": "Кампус", "kind": "campus", "image": "campus.png"});
+        assert!(MapCatalog::from_json(&json!([campus, campus]).to_string()).is_err());
+        assert!(MapCatalog::from_json("[]").is_err());
+        let floor = json!({"id": "uk3-2", "title": "УК3", "kind": "floor", "building": "УК3", "image": "uk3.png"});
+        assert!(MapCatalog::from_json(&json!([campus, floor]).to_string()).is_err());
+    }
+
+    #[test]
+    fn test_shipped_maps(){
+        let data = std::fs::read_to_string("maps.json").unwrap();
+        let catalog = MapCatalog::from_json(&data).unwrap();
+        assert_eq!(catalog.general(), &MapCatalog::default().maps()[0]);
+    }
+}
@@ -0,0 +1,502 @@
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::RwLock;
+use super::error::StoreError;
+use super::images::{content_hash, decode_image, encode_base64};
+use super::location::ClassroomLocation;
+use super::models::{parse_classroom_data, parse_image_data, CampusImage, ClassroomData};
+use super::seed::{dataset_checksum, DatasetVersion};
+use super::store::{Dataset, NavigationStore, StoreStatus};
+
+/// Хранилище навигационных данных в памяти процесса
+///
+/// # Что делает?
+/// - Разбирает те же JSON-данные об аудиториях и картинках, что и DBClient
+/// - Отвечает на запросы без внешних сервисов (CI, локальный запуск)
+/// - Принимает правки через админские методы, но не сохраняет их между запусками
+#[derive(Debug)]
+pub struct MemoryStore{
+    /// Все данные под одной блокировкой: перезагрузка подменяет их целиком,
+    /// и читатели не видят аудитории из одного набора с картинками из другого
+    data: RwLock<StoreData>,
+}
+
+#[derive(Debug)]
+struct StoreData{
+    classrooms: Vec<ClassroomData>,
+    /// Имя аудитории -> ее место в classrooms, чтобы не перебирать весь список на каждый запрос
+    index: HashMap<String, usize>,
+    images: ImageTable,
+    /// Версия растет при каждой перезагрузке измененных данных, а edits - при каждой правке, как в DBClient
+    dataset: DatasetVersion,
+}
+
+impl StoreData{
+    fn new(classrooms: Vec<ClassroomData>, images: ImageTable, dataset: DatasetVersion) -> Self {
+        let mut data = Self{classrooms, index: HashMap::new(), images, dataset};
+        data.reindex();
+        data
+    }
+
+    /// Перестраивает индекс по именам, из повторяющихся имен находится первое
+    fn reindex(&mut self) {
+        self.index.clear();
+        for (position, classroom) in self.classrooms.iter().enumerate() {
+            self.index.entry(classroom.classroom.to_owned()).or_insert(position);
+        }
+    }
+
+    fn get_classroom(&self, classroom_name: &str) -> Option<&ClassroomData> {
+        self.classrooms.get(*self.index.get(classroom_name)?)
+    }
+}
+
+/// Картинки по хэшу содержимого с таблицей названий, как в DBClient
+#[derive(Debug, Default)]
+struct ImageTable{
+    /// Название картинки -> хэш содержимого
+    aliases: HashMap<String, String>,
+    /// Хэш -> содержимое в Base64, чтобы не кодировать его на каждый запрос
+    blobs: HashMap<String, String>,
+}
+
+impl ImageTable{
+    fn get(&self, image_name: &str) -> Option<CampusImage> {
+        let hash = self.aliases.get(image_name)?;
+        Some(CampusImage{name: image_name.to_owned(), value: self.blobs.get(hash)?.to_owned()})
+    }
+
+    /// Записывает картинку под названием, одинаковое содержимое сохраняется один раз
+    fn put(&mut self, image: &CampusImage) -> Result<(), StoreError> {
+        let bytes = decode_image(image)?;
+        let hash = content_hash(&bytes);
+        self.blobs.entry(hash.to_owned()).or_insert_with(||encode_base64(&bytes));
+        if let Some(previous) = self.aliases.insert(image.name.to_owned(), hash) {
+            self.release(&previous);
+        }
+        Ok(())
+    }
+
+    fn remove(&mut self, image_name: &str) -> bool {
+        match self.aliases.remove(image_name) {
+            Some(hash) => {
+                self.release(&hash);
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Удаляет содержимое, на которое больше не ссылается ни одно название
+    fn release(&mut self, hash: &str) {
+        if !self.aliases.values().any(|x|x == hash) {
+            self.blobs.remove(hash);
+        }
+    }
+}
+
+impl MemoryStore{
+    /// Создает новое хранилище в памяти
+    ///
+    /// # Аргументы
+    /// - classroom_data: навигационные данные для аудиторий в виде ClassroomData в JSON
+    /// - image_data: картинки для аудиторий в виде CampusImage в JSON
+    pub fn new(classroom_data: String, image_data: String) -> Result<Self, StoreError> {
+        let dataset = DatasetVersion::first(dataset_checksum(&classroom_data, &image_data));
+        Self::from_dataset(Dataset{
+            classrooms: parse_classroom_data(&classroom_data)?,
+            images: parse_image_data(&image_data)?,
+            version: Some(dataset)})
+    }
+
+    /// Создает хранилище из уже разобранных данных, например из копии Монго-базы
+    ///
+    /// # Примечание:
+    /// Если версия набора данных неизвестна, она считается по самим данным
+    pub fn from_dataset(data: Dataset) -> Result<Self, StoreError> {
+        let dataset = match data.version {
+            Some(val) => val,
+            None => DatasetVersion::first(
+                dataset_checksum(&serde_json::to_string(&data.classrooms)?, &serde_json::to_string(&data.images)?)),
+        };
+        let mut images = ImageTable::default();
+        for image in &data.images {
+            images.put(image)?;
+        }
+        Ok(Self{data: RwLock::new(StoreData::new(data.classrooms, images, dataset))})
+    }
+
+}
+
+#[async_trait]
+impl NavigationStore for MemoryStore{
+    async fn get_classroom_list(&self) -> Result<String, StoreError> {
+        let json_data = serde_json::to_string(&self.get_classroom_names().await?)?;
+        Ok(json_data)
+    }
+
+    async fn get_classroom_names(&self) -> Result<Vec<String>, StoreError> {
+        let classrooms: Vec<String> = self.data
+            .read()
+            .unwrap()
+            .classrooms
+            .iter()
+            .map(|x|x.classroom.to_owned())
+            .collect();
+        Ok(classrooms)
+    }
+
+    async fn get_classroom(&self, classroom_name: String) -> Result<ClassroomData, StoreError> {
+        match self.data.read().unwrap().get_classroom(&classroom_name){
+            Some(classroom) => Ok(classroom.clone()),
+            None => Err(StoreError::ClassroomNotFound(classroom_name)),
+        }
+    }
+
+    async fn get_classroom_data(&self, classroom_name: String) -> Result<String, StoreError> {
+        let mut needed_classroom = self.get_classroom(classroom_name).await?;
+
+        needed_classroom.images = self.get_campus_images(&needed_classroom.images).await?
+            .into_iter()
+            .map(|elem|elem.value)
+            .collect();
+        let result = serde_json::to_string(&needed_classroom)?;
+        Ok(result)
+    }
+
+    async fn get_campus_image(&self, image_name: String) -> Result<CampusImage, StoreError> {
+        match self.data.read().unwrap().images.get(&image_name){
+            Some(image) => Ok(image),
+            None => Err(StoreError::ImageNotFound(image_name)),
+        }
+    }
+
+    async fn get_campus_images(&self, image_names: &[String]) -> Result<Vec<CampusImage>, StoreError> {
+        let data = self.data.read().unwrap();
+        let needed_images: Vec<CampusImage> = image_names
+            .iter()
+            .filter_map(|name|data.images.get(name))
+            .collect();
+        if needed_images.is_empty(){
+            return Err(StoreError::ImageNotFound(image_names.join(", ")));
+        }
+        Ok(needed_images)
+    }
+
+    async fn has_image(&self, image_name: String) -> Result<bool, StoreError> {
+        Ok(self.data.read().unwrap().images.aliases.contains_key(&image_name))
+    }
+
+    async fn get_map(&self, image_name: String) -> Result<String, StoreError> {
+        let general_images: Vec<String> = self.get_campus_images(&[image_name]).await?
+            .into_iter()
+            .map(|elem|elem.value)
+            .collect();
+        let result = serde_json::to_string(&general_images)?;
+        Ok(result)
+    }
+
+    async fn get_classrooms_with_image(&self, image_name: String) -> Result<Vec<String>, StoreError> {
+        let classrooms: Vec<String> = self.data
+            .read()
+            .unwrap()
+            .classrooms
+            .iter()
+            .filter(|classroom|classroom.images.contains(&image_name))
+            .map(|classroom|classroom.classroom.to_owned())
+            .collect();
+        Ok(classrooms)
+    }
+
+    async fn insert_classroom(&self, mut classroom: ClassroomData) -> Result<(), StoreError> {
+        let mut data = self.data.write().unwrap();
+        if data.index.contains_key(&classroom.classroom) {
+            return Err(StoreError::Conflict(format!("classroom {} already exists", classroom.classroom)));
+        }
+        classroom.location = ClassroomLocation::parse(&classroom.classroom);
+        let position = data.classrooms.len();
+        data.index.insert(classroom.classroom.to_owned(), position);
+        data.classrooms.push(classroom);
+        data.dataset.edits += 1;
+        Ok(())
+    }
+
+    async fn replace_classroom(&self, classroom_name: String, mut classroom: ClassroomData) -> Result<(), StoreError> {
+        let mut data = self.data.write().unwrap();
+        if classroom.classroom != classroom_name && data.index.contains_key(&classroom.classroom) {
+            return Err(StoreError::Conflict(format!("classroom {} already exists", classroom.classroom)));
+        }
+        let position = match data.index.get(&classroom_name) {
+            Some(val) => *val,
+            None => return Err(StoreError::ClassroomNotFound(classroom_name)),
+        };
+        let renamed = classroom.classroom != classroom_name;
+        classroom.location = ClassroomLocation::parse(&classroom.classroom);
+        data.classrooms[position] = classroom;
+        if renamed {
+            data.reindex();
+        }
+        data.dataset.edits += 1;
+        Ok(())
+    }
+
+    async fn delete_classroom(&self, classroom_name: String) -> Result<(), StoreError> {
+        let mut data = self.data.write().unwrap();
+        if !data.index.contains_key(&classroom_name) {
+            return Err(StoreError::ClassroomNotFound(classroom_name));
+        }
+        data.classrooms.retain(|x|x.classroom != classroom_name);
+        data.reindex();
+        data.dataset.edits += 1;
+        Ok(())
+    }
+
+    async fn insert_image(&self, image: CampusImage) -> Result<(), StoreError> {
+        let mut data = self.data.write().unwrap();
+        let images = &mut data.images;
+        if images.aliases.contains_key(&image.name) {
+            return Err(StoreError::Conflict(format!("image {} already exists", image.name)));
+        }
+        images.put(&image)?;
+        data.dataset.edits += 1;
+        Ok(())
+    }
+
+    async fn replace_image(&self, image: CampusImage) -> Result<(), StoreError> {
+        let mut data = self.data.write().unwrap();
+        let images = &mut data.images;
+        if !images.aliases.contains_key(&image.name) {
+            return Err(StoreError::ImageNotFound(image.name));
+        }
+        images.put(&image)?;
+        data.dataset.edits += 1;
+        Ok(())
+    }
+
+    async fn delete_image(&self, image_name: String) -> Result<(), StoreError> {
+        let mut data = self.data.write().unwrap();
+        match data.images.remove(&image_name) {
+            true => {
+                data.dataset.edits += 1;
+                Ok(())
+            },
+            false => Err(StoreError::ImageNotFound(image_name)),
+        }
+    }
+
+    async fn reload(&self, classroom_data: String, image_data: String) -> Result<(), StoreError> {
+        let new_data = Self::new(classroom_data, image_data)?.data.into_inner().unwrap();
+        let mut data = self.data.write().unwrap();
+        let dataset = data.dataset.next(new_data.dataset.checksum.to_owned());
+        *data = StoreData{dataset, ..new_data};
+        Ok(())
+    }
+
+    async fn export(&self) -> Result<Dataset, StoreError> {
+        let data = self.data.read().unwrap();
+        let mut image_names: Vec<&String> = data.images.aliases.keys().collect();
+        image_names.sort();
+        Ok(Dataset{
+            classrooms: data.classrooms.clone(),
+            images: image_names.into_iter().filter_map(|name|data.images.get(name)).collect(),
+            version: Some(data.dataset.clone())})
+    }
+
+    async fn status(&self) -> Result<StoreStatus, StoreError> {
+        let data = self.data.read().unwrap();
+        Ok(StoreStatus{
+            classrooms: data.classrooms.len() as u64,
+            images: data.images.aliases.len() as u64,
+            dataset: Some(data.dataset.clone()),
+            degraded: None})
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use serde_json::json;
+    use super::*;
+
+    fn valid_classroom_data() -> String {
+        json!([{
+                "classroom": "УК3 104",
+                "description": "Крутая аудитория",
+                "images": ["UK3-left.png", "UK3-right.png"]
+            },
+            {
+                "classroom": "УК3 205",
+                "description": "Менее крутая аудитория",
+                "images": ["UK3-left.png", "UK3-right.png"]
+            },
+        ]).to_string()
+    }
+
+    fn valid_image_data() -> String {
+        json!([{
+                "name": "UK3-left.png",
+                "value": "bibabob0",
+            },
+            {
+                "name": "UK3-right.png",
+                "value": "pipupap0",
+            },
+            {
+                "name": "general_map.png",
+                "value": "mapmapma",
+            },
+            ]).to_string()
+    }
+
+    fn store() -> MemoryStore {
+        match MemoryStore::new(valid_classroom_data(), valid_image_data()) {
+            Ok(val) => val,
+            Err(e) => panic!("Store init failed: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn test_store_init_bad_json(){
+        assert!(MemoryStore::new("not json".to_owned(), valid_image_data()).is_err());
+        assert!(MemoryStore::new(valid_classroom_data(), "[{}]".to_owned()).is_err());
+        let image_data = json!([{"name": "UK3-left.png", "value": "not base64!"}]).to_string();
+        assert!(matches!(MemoryStore::new(valid_classroom_data(), image_data), Err(StoreError::InvalidData(_))));
+    }
+
+    #[actix_web::test]
+    async fn test_classroom_list(){
+        let list = store().get_classroom_list().await.unwrap();
+        assert_eq!(json!(["УК3 104", "УК3 205"]).to_string(), list);
+    }
+
+    #[actix_web::test]
+    async fn test_classroom_data_ok(){
+        let data = store().get_classroom_data("УК3 104".to_string()).await.unwrap();
+        let data: serde_json::Value = serde_json::from_str(&data).unwrap();
+        let value = json!({
+            "classroom": "УК3 104",
+            "description": "Крутая аудитория",
+            "images": ["bibabob0", "pipupap0"],
+            "location": {
+                "building": "УК3",
+                "floor": 1,
+                "room": "104",
+                "wing": null,
+                "suffix": null,
+                "kind": "classroom",
+            },
+        });
+        assert_eq!(value, data);
+    }
+
+    #[actix_web::test]
+    async fn test_classroom_data_bad(){
+        assert!(store().get_classroom_data("УК4 104".to_string()).await.is_err());
+    }
+
+    #[actix_web::test]
+    async fn test_campus_image(){
+        let image = store().get_campus_image("UK3-right.png".to_string()).await.unwrap();
+        assert_eq!(image.value, "pipupap0");
+        assert!(store().get_campus_image("UK3-back.png".to_string()).await.is_err());
+        assert!(store().has_image("UK3-right.png".to_string()).await.unwrap());
+        assert!(!store().has_image("UK3-back.png".to_string()).await.unwrap());
+    }
+
+    #[actix_web::test]
+    async fn test_campus_images(){
+        let names = ["UK3-right.png", "UK3-back.png", "UK3-left.png"].map(String::from);
+        let images = store().get_campus_images(&names).await.unwrap();
+        let values: Vec<&str> = images.iter().map(|x|x.value.as_str()).collect();
+        assert_eq!(values, ["pipupap0", "bibabob0"]);
+        let missing = store().get_campus_images(&["UK3-back.png".to_owned()]).await;
+        assert!(matches!(missing, Err(StoreError::ImageNotFound(_))));
+    }
+
+    #[actix_web::test]
+    async fn test_map(){
+        let map = store().get_map("general_map.png".to_owned()).await.unwrap();
+        assert_eq!(json!(["mapmapma"]).to_string(), map);
+    }
+
+    #[actix_web::test]
+    async fn test_classroom_editing(){
+        let store = store();
+        let mut classroom = store.get_classroom("УК3 205".to_string()).await.unwrap();
+        classroom.classroom = "ГУК 305".to_owned();
+        assert!(store.insert_classroom(classroom.clone()).await.is_ok());
+        assert!(store.insert_classroom(classroom.clone()).await.is_err());
+        assert_eq!(store.get_classroom("ГУК 305".to_string()).await.unwrap().location.building.as_deref(), Some("ГУК"));
+
+        classroom.classroom = "УК3 104".to_owned();
+        assert!(store.replace_classroom("ГУК 305".to_owned(), classroom.clone()).await.is_err());
+        classroom.classroom = "ГУК 306".to_owned();
+        classroom.description = "Новое описание".to_owned();
+        store.replace_classroom("ГУК 305".to_owned(), classroom).await.unwrap();
+        assert_eq!(store.get_classroom("ГУК 306".to_string()).await.unwrap().description, "Новое описание");
+        assert!(store.get_classroom("ГУК 305".to_string()).await.is_err());
+
+        store.delete_classroom("ГУК 306".to_owned()).await.unwrap();
+        assert!(store.delete_classroom("ГУК 306".to_owned()).await.is_err());
+
+        store.delete_classroom("УК3 104".to_owned()).await.unwrap();
+        assert_eq!(store.get_classroom("УК3 205".to_string()).await.unwrap().classroom, "УК3 205");
+    }
+
+    #[actix_web::test]
+    async fn test_image_editing(){
+        let store = store();
+        let image = CampusImage{name: "UK3-back.png".to_owned(), value: "bababab0".to_owned()};
+        store.insert_image(image.clone()).await.unwrap();
+        assert!(store.insert_image(image).await.is_err());
+        store.replace_image(CampusImage{name: "UK3-back.png".to_owned(), value: "bWFw".to_owned()}).await.unwrap();
+        assert_eq!(store.get_campus_image("UK3-back.png".to_owned()).await.unwrap().value, "bWFw");
+
+        assert_eq!(store.get_classrooms_with_image("UK3-left.png".to_owned()).await.unwrap(), vec!["УК3 104", "УК3 205"]);
+        store.delete_image("UK3-back.png".to_owned()).await.unwrap();
+        assert!(store.replace_image(CampusImage{name: "UK3-back.png".to_owned(), value: "bWFw".to_owned()}).await.is_err());
+    }
+
+    #[actix_web::test]
+    async fn test_status(){
+        let store = store();
+        let status = store.status().await.unwrap();
+        assert_eq!((status.classrooms, status.images), (2, 3));
+        assert_eq!(status.dataset.as_ref().unwrap().version, 1);
+
+        store.reload(valid_classroom_data(), valid_image_data()).await.unwrap();
+        assert_eq!(store.status().await.unwrap().dataset, status.dataset);
+        store.reload("[]".to_owned(), "[]".to_owned()).await.unwrap();
+        let status = store.status().await.unwrap();
+        assert_eq!((status.classrooms, status.images, status.dataset.unwrap().version), (0, 0, 2));
+    }
+
+    #[actix_web::test]
+    async fn test_image_deduplication(){
+        let image_data = json!([
+            {"name": "UK3-left.png", "value": "bibabob0"},
+            {"name": "UK3-left-copy.png", "value": "data:image/png;base64,bibabob0"},
+            {"name": "UK3-right.png", "value": "pipupap0"},
+        ]).to_string();
+        let store = MemoryStore::new(valid_classroom_data(), image_data).unwrap();
+        assert_eq!(store.data.read().unwrap().images.blobs.len(), 2);
+        assert_eq!(store.get_campus_image("UK3-left-copy.png".to_owned()).await.unwrap().value, "bibabob0");
+
+        store.delete_image("UK3-left.png".to_owned()).await.unwrap();
+        assert_eq!(store.get_campus_image("UK3-left-copy.png".to_owned()).await.unwrap().value, "bibabob0");
+        store.replace_image(CampusImage{name: "UK3-left-copy.png".to_owned(), value: "pipupap0".to_owned()}).await.unwrap();
+        assert_eq!(store.data.read().unwrap().images.blobs.len(), 1);
+        assert!(store.insert_image(CampusImage{name: "a.png".to_owned(), value: "bibabob!".to_owned()}).await.is_err());
+    }
+
+    #[actix_web::test]
+    async fn test_wrapped_base64(){
+        let image_data = json!([
+            {"name": "UK3-left.png", "value": "bibabob0\r\npipupap0\n"},
+            {"name": "UK3-right.png", "value": "pipupap0"},
+        ]).to_string();
+        let store = MemoryStore::new(valid_classroom_data(), image_data).unwrap();
+        assert_eq!(store.get_campus_image("UK3-left.png".to_owned()).await.unwrap().value, "bibabob0pipupap0");
+        store.reload(valid_classroom_data(), json!([{"name": "UK3-left.png", "value": "bibabob0\n pipupap0"}]).to_string()).await.unwrap();
+        assert_eq!(store.get_campus_image("UK3-left.png".to_owned()).await.unwrap().value, "bibabob0pipupap0");
+    }
+}
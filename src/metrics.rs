@@ -0,0 +1,410 @@
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse};
+use actix_web::{get, HttpRequest, HttpResponse, web};
+use async_trait::async_trait;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::Write;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use super::cache::ResponseCache;
+use super::error::StoreError;
+use super::location::ClassroomLocation;
+use super::models::{CampusImage, ClassroomData};
+use super::server::SharedStore;
+use super::store::{Dataset, NavigationStore, StoreStatus};
+use super::thumbnails::VariantCache;
+
+/// Границы корзин гистограмм задержек в секундах
+const LATENCY_BUCKETS: [f64; 12] = [0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0];
+
+/// Гистограмма задержек в формате Prometheus
+#[derive(Debug, Clone, Default)]
+struct Histogram{
+    /// Сколько наблюдений попало в каждую корзину LATENCY_BUCKETS, не нарастающим итогом
+    buckets: [u64; LATENCY_BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+impl Histogram{
+    fn observe(&mut self, elapsed: Duration) {
+        let seconds = elapsed.as_secs_f64();
+        if let Some(idx) = LATENCY_BUCKETS.iter().position(|bound|seconds <= *bound) {
+            self.buckets[idx] += 1;
+        }
+        self.count += 1;
+        self.sum += seconds;
+    }
+
+    /// Пишет строки _bucket, _sum и _count
+    ///
+    /// # Аргументы:
+    /// - labels: метки гистограммы уже в виде key="value", через запятую
+    fn render(&self, out: &mut String, name: &str, labels: &str) {
+        let separator = if labels.is_empty() {""} else {","};
+        let mut cumulative = 0;
+        for (bound, count) in LATENCY_BUCKETS.iter().zip(self.buckets) {
+            cumulative += count;
+            let _ = writeln!(out, "{}_bucket{{{}{}le=\"{}\"}} {}", name, labels, separator, bound, cumulative);
+        }
+        let _ = writeln!(out, "{}_bucket{{{}{}le=\"+Inf\"}} {}", name, labels, separator, self.count);
+        let _ = writeln!(out, "{}_sum{{{}}} {}", name, labels, self.sum);
+        let _ = writeln!(out, "{}_count{{{}}} {}", name, labels, self.count);
+    }
+}
+
+/// Метки запроса: метод, шаблон маршрута ("/images/{name}") и код ответа
+type RequestLabels = (String, String, u16);
+
+/// Метрики сервиса для /metrics
+///
+/// # Что делает?
+/// - Считает запросы и их задержки по маршрутам и кодам ответа (см. track)
+/// - Считает задержки хранилища по операциям (см. MeteredStore)
+/// - Считает ненайденные аудитории по корпусам
+///
+/// # Примечание:
+/// Счетчики кэшей и размер набора данных не хранятся, а читаются при каждом запросе /metrics
+#[derive(Debug, Default)]
+pub struct Metrics{
+    requests: Mutex<BTreeMap<RequestLabels, Histogram>>,
+    store_operations: Mutex<BTreeMap<&'static str, Histogram>>,
+    classrooms_not_found: Mutex<BTreeMap<String, u64>>,
+}
+
+impl Metrics{
+    pub fn observe_request(&self, method: &str, route: &str, status: u16, elapsed: Duration) {
+        self.requests.lock().unwrap()
+            .entry((method.to_owned(), route.to_owned(), status))
+            .or_default()
+            .observe(elapsed);
+    }
+
+    pub fn observe_store_operation(&self, operation: &'static str, elapsed: Duration) {
+        self.store_operations.lock().unwrap()
+            .entry(operation)
+            .or_default()
+            .observe(elapsed);
+    }
+
+    /// Запоминает, что студент искал аудиторию, которой нет
+    ///
+    /// # Аргументы:
+    /// - classroom_name: имя из запроса
+    /// - classroom_names: все имена аудиторий, по ним определяются известные корпуса
+    ///
+    /// # Примечание:
+    /// Метка building - корпус из запроса в верхнем регистре, если такой корпус есть в данных,
+    /// "other" для незнакомых корпусов и "unknown", если имя не разобралось.
+    /// Так число меток не растет от произвольного ввода
+    pub fn classroom_not_found(&self, classroom_name: &str, classroom_names: &[String]) {
+        let building = match ClassroomLocation::parse(classroom_name).building {
+            Some(building) => {
+                let building = building.to_uppercase();
+                let known: HashSet<String> = classroom_names.iter()
+                    .filter_map(|name|ClassroomLocation::parse(name).building)
+                    .map(|x|x.to_uppercase())
+                    .collect();
+                if known.contains(&building) {building} else {"other".to_owned()}
+            },
+            None => "unknown".to_owned(),
+        };
+        *self.classrooms_not_found.lock().unwrap().entry(building).or_default() += 1;
+    }
+
+    /// Выдает метрики в текстовом формате Prometheus
+    ///
+    /// # Аргументы:
+    /// - cache: кэш ответов, если он включен
+    /// - variants: кэш уменьшенных картинок
+    /// - store: состояние хранилища, None если оно недоступно
+    pub fn render(&self, cache: Option<&ResponseCache>, variants: &VariantCache, store: Option<&StoreStatus>) -> String {
+        let mut out = String::new();
+
+        header(&mut out, "navigation_http_requests_total", "HTTP requests by route and status", "counter");
+        let requests = self.requests.lock().unwrap();
+        for ((method, route, status), histogram) in requests.iter() {
+            let _ = writeln!(out, "navigation_http_requests_total{{{}}} {}", request_labels(method, route, *status), histogram.count);
+        }
+        header(&mut out, "navigation_http_request_duration_seconds", "HTTP request latency by route and status", "histogram");
+        for ((method, route, status), histogram) in requests.iter() {
+            histogram.render(&mut out, "navigation_http_request_duration_seconds", &request_labels(method, route, *status));
+        }
+        drop(requests);
+
+        header(&mut out, "navigation_store_operation_duration_seconds", "Storage (MongoDB) call latency by operation", "histogram");
+        for (operation, histogram) in self.store_operations.lock().unwrap().iter() {
+            histogram.render(&mut out, "navigation_store_operation_duration_seconds", &format!("operation=\"{}\"", operation));
+        }
+
+        header(&mut out, "navigation_classroom_not_found_total", "Requested classrooms that do not exist, by building", "counter");
+        for (building, count) in self.classrooms_not_found.lock().unwrap().iter() {
+            let _ = writeln!(out, "navigation_classroom_not_found_total{{building=\"{}\"}} {}", escape(building), count);
+        }
+
+        if let Some(cache) = cache {
+            let stats = cache.stats();
+            gauge(&mut out, "navigation_cache_hits_total", "Response cache hits", "counter", stats.hits as f64);
+            gauge(&mut out, "navigation_cache_misses_total", "Response cache misses", "counter", stats.misses as f64);
+            gauge(&mut out, "navigation_cache_hit_ratio", "Response cache hits / lookups", "gauge", ratio(stats.hits, stats.misses));
+            gauge(&mut out, "navigation_cache_entries", "Cached responses", "gauge", stats.entries as f64);
+            gauge(&mut out, "navigation_cache_bytes", "Approximate size of cached responses", "gauge", stats.bytes as f64);
+        }
+        let stats = variants.stats();
+        gauge(&mut out, "navigation_image_variant_cache_hit_ratio", "Resized image cache hits / lookups", "gauge", ratio(stats.hits, stats.misses));
+        gauge(&mut out, "navigation_image_variant_cache_bytes", "Bytes of cached resized images", "gauge", stats.bytes as f64);
+
+        gauge(&mut out, "navigation_store_up", "Whether the storage answers", "gauge", f64::from(u8::from(store.is_some())));
+        if let Some(store) = store {
+            gauge(&mut out, "navigation_store_degraded", "Whether the data is served from the in-memory copy", "gauge", f64::from(u8::from(store.degraded.is_some())));
+            gauge(&mut out, "navigation_dataset_classrooms", "Classrooms in the dataset", "gauge", store.classrooms as f64);
+            gauge(&mut out, "navigation_dataset_images", "Image names in the dataset", "gauge", store.images as f64);
+            if let Some(dataset) = &store.dataset {
+                gauge(&mut out, "navigation_dataset_version", "Dataset version, grows on every change", "gauge", dataset.version as f64);
+            }
+        }
+        out
+    }
+}
+
+fn header(out: &mut String, name: &str, help: &str, kind: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn gauge(out: &mut String, name: &str, help: &str, kind: &str, value: f64) {
+    header(out, name, help, kind);
+    let _ = writeln!(out, "{} {}", name, value);
+}
+
+fn ratio(hits: u64, misses: u64) -> f64 {
+    match hits + misses {
+        0 => 0.0,
+        total => hits as f64 / total as f64,
+    }
+}
+
+fn request_labels(method: &str, route: &str, status: u16) -> String {
+    format!("method=\"{}\",route=\"{}\",status=\"{}\"", escape(method), escape(route), status)
+}
+
+/// Экранирует значение метки по правилам текстового формата Prometheus
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// Middleware для App::wrap_fn: замеряет запрос и записывает его в Metrics из app_data
+///
+/// # Примечание:
+/// Маршрут берется по шаблону ("/images/{name}"), а не по пути, чтобы не плодить метки.
+/// Запросы мимо всех маршрутов пишутся как "unmatched"
+pub fn track<S, B>(request: ServiceRequest, service: &S) -> impl Future<Output = Result<ServiceResponse<B>, actix_web::Error>>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error>,
+{
+    let started = Instant::now();
+    let metrics = request.app_data::<web::Data<Metrics>>().cloned();
+    let response = service.call(request);
+    async move {
+        let response = response.await?;
+        if let Some(metrics) = metrics {
+            let request = response.request();
+            let route = request.match_pattern().unwrap_or_else(||"unmatched".to_owned());
+            metrics.observe_request(request.method().as_str(), &route, response.status().as_u16(), started.elapsed());
+        }
+        Ok(response)
+    }
+}
+
+#[get("/metrics")]
+async fn get_metrics(request: HttpRequest, metrics: web::Data<Metrics>, variants: web::Data<VariantCache>, db_client: SharedStore) -> HttpResponse {
+    let cache = request.app_data::<web::Data<ResponseCache>>();
+    let store = db_client.status().await.ok();
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4; charset=utf-8")
+        .body(metrics.render(cache.map(|x|x.get_ref()), &variants, store.as_ref()))
+}
+
+/// Регистрирует /metrics
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(get_metrics);
+}
+
+/// Хранилище, которое замеряет задержки другого хранилища
+///
+/// # Примечание:
+/// Ставится сразу над DBClient/MemoryStore, под кэшем, чтобы мерить только настоящие запросы к базе
+pub struct MeteredStore{
+    inner: Arc<dyn NavigationStore>,
+    metrics: Arc<Metrics>,
+}
+
+impl MeteredStore{
+    pub fn new(inner: Arc<dyn NavigationStore>, metrics: Arc<Metrics>) -> Self {
+        Self{inner, metrics}
+    }
+
+    async fn measure<T, F>(&self, operation: &'static str, call: F) -> Result<T, StoreError>
+    where
+        F: Future<Output = Result<T, StoreError>>,
+    {
+        let started = Instant::now();
+        let result = call.await;
+        let elapsed = started.elapsed();
+        self.metrics.observe_store_operation(operation, elapsed);
+        tracing::debug!(operation, elapsed_ms = elapsed.as_secs_f64() * 1000.0, ok = result.is_ok(), "store call");
+        result
+    }
+}
+
+#[async_trait]
+impl NavigationStore for MeteredStore{
+    async fn get_classroom_list(&self) -> Result<String, StoreError> {
+        self.measure("get_classroom_list", self.inner.get_classroom_list()).await
+    }
+
+    async fn get_classroom_names(&self) -> Result<Vec<String>, StoreError> {
+        self.measure("get_classroom_names", self.inner.get_classroom_names()).await
+    }
+
+    async fn get_classroom(&self, classroom_name: String) -> Result<ClassroomData, StoreError> {
+        self.measure("get_classroom", self.inner.get_classroom(classroom_name)).await
+    }
+
+    async fn get_classroom_data(&self, classroom_name: String) -> Result<String, StoreError> {
+        self.measure("get_classroom_data", self.inner.get_classroom_data(classroom_name)).await
+    }
+
+    async fn get_campus_image(&self, image_name: String) -> Result<CampusImage, StoreError> {
+        self.measure("get_campus_image", self.inner.get_campus_image(image_name)).await
+    }
+
+    async fn get_campus_images(&self, image_names: &[String]) -> Result<Vec<CampusImage>, StoreError> {
+        self.measure("get_campus_images", self.inner.get_campus_images(image_names)).await
+    }
+
+    async fn has_image(&self, image_name: String) -> Result<bool, StoreError> {
+        self.measure("has_image", self.inner.has_image(image_name)).await
+    }
+
+    async fn get_map(&self, image_name: String) -> Result<String, StoreError> {
+        self.measure("get_map", self.inner.get_map(image_name)).await
+    }
+
+    async fn get_classrooms_with_image(&self, image_name: String) -> Result<Vec<String>, StoreError> {
+        self.measure("get_classrooms_with_image", self.inner.get_classrooms_with_image(image_name)).await
+    }
+
+    async fn insert_classroom(&self, classroom: ClassroomData) -> Result<(), StoreError> {
+        self.measure("insert_classroom", self.inner.insert_classroom(classroom)).await
+    }
+
+    async fn replace_classroom(&self, classroom_name: String, classroom: ClassroomData) -> Result<(), StoreError> {
+        self.measure("replace_classroom", self.inner.replace_classroom(classroom_name, classroom)).await
+    }
+
+    async fn delete_classroom(&self, classroom_name: String) -> Result<(), StoreError> {
+        self.measure("delete_classroom", self.inner.delete_classroom(classroom_name)).await
+    }
+
+    async fn insert_image(&self, image: CampusImage) -> Result<(), StoreError> {
+        self.measure("insert_image", self.inner.insert_image(image)).await
+    }
+
+    async fn replace_image(&self, image: CampusImage) -> Result<(), StoreError> {
+        self.measure("replace_image", self.inner.replace_image(image)).await
+    }
+
+    async fn delete_image(&self, image_name: String) -> Result<(), StoreError> {
+        self.measure("delete_image", self.inner.delete_image(image_name)).await
+    }
+
+    async fn reload(&self, classroom_data: String, image_data: String) -> Result<(), StoreError> {
+        self.measure("reload", self.inner.reload(classroom_data, image_data)).await
+    }
+
+    async fn export(&self) -> Result<Dataset, StoreError> {
+        self.measure("export", self.inner.export()).await
+    }
+
+    async fn status(&self) -> Result<StoreStatus, StoreError> {
+        self.measure("status", self.inner.status()).await
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use serde_json::json;
+    use serial_test::serial;
+    use actix_service::Service as _;
+    use actix_web::http::StatusCode;
+    use actix_web::test::TestRequest;
+    use super::*;
+    use super::super::server::{Server, StorageKind};
+
+    #[test]
+    fn test_histogram(){
+        let mut histogram = Histogram::default();
+        histogram.observe(Duration::from_millis(3));
+        histogram.observe(Duration::from_millis(30));
+        histogram.observe(Duration::from_secs(10));
+        let mut out = String::new();
+        histogram.render(&mut out, "latency", "route=\"/map\"");
+        assert!(out.contains("latency_bucket{route=\"/map\",le=\"0.001\"} 0\n"));
+        assert!(out.contains("latency_bucket{route=\"/map\",le=\"0.005\"} 1\n"));
+        assert!(out.contains("latency_bucket{route=\"/map\",le=\"0.05\"} 2\n"));
+        assert!(out.contains("latency_bucket{route=\"/map\",le=\"5\"} 2\n"));
+        assert!(out.contains("latency_bucket{route=\"/map\",le=\"+Inf\"} 3\n"));
+        assert!(out.contains("latency_count{route=\"/map\"} 3\n"));
+    }
+
+    #[test]
+    fn test_not_found_buildings(){
+        let metrics = Metrics::default();
+        let names = vec!["УК3 104".to_owned(), "ГУК 305".to_owned()];
+        metrics.classroom_not_found("ук3 999", &names);
+        metrics.classroom_not_found("УК3 998", &names);
+        metrics.classroom_not_found("Дворец спорта 1", &names);
+        metrics.classroom_not_found("столовая", &names);
+        let out = metrics.render(None, &VariantCache::new(0), None);
+        assert!(out.contains("navigation_classroom_not_found_total{building=\"УК3\"} 2\n"));
+        assert!(out.contains("navigation_classroom_not_found_total{building=\"other\"} 1\n"));
+        assert!(out.contains("navigation_classroom_not_found_total{building=\"unknown\"} 1\n"));
+        assert!(out.contains("navigation_store_up 0\n"));
+    }
+
+    #[test]
+    fn test_escape(){
+        assert_eq!(escape("a\"b\\c\nd"), "a\\\"b\\\\c\\nd");
+    }
+
+    #[actix_web::test]
+    #[serial]
+    async fn test_metrics_endpoint(){
+        let classroom_data = json!([{"classroom": "УК3 104", "description": "", "images": []}]).to_string();
+        let app = Server::builder()
+            .classroom_data(classroom_data)
+            .storage(StorageKind::Memory)
+            .build()
+            .test_start()
+            .await
+            .unwrap();
+        for name in ["УК3 104", "УК3 999"] {
+            let uri = format!("/classroom?name={}&images=url", urlencoding::encode(name));
+            app.call(TestRequest::with_uri(&uri).to_request()).await.unwrap();
+        }
+        app.call(TestRequest::with_uri("/nowhere").to_request()).await.unwrap();
+
+        let res = app.call(TestRequest::with_uri("/metrics").to_request()).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert!(res.headers().get(actix_web::http::header::CONTENT_TYPE).unwrap().to_str().unwrap().starts_with("text/plain; version=0.0.4"));
+        let body = String::from_utf8(actix_web::test::read_body(res).await.to_vec()).unwrap();
+        assert!(body.contains("navigation_http_requests_total{method=\"GET\",route=\"/classroom\",status=\"200\"} 1\n"));
+        assert!(body.contains("navigation_http_requests_total{method=\"GET\",route=\"/classroom\",status=\"404\"} 1\n"));
+        assert!(body.contains("navigation_http_requests_total{method=\"GET\",route=\"unmatched\",status=\"404\"} 1\n"));
+        assert!(body.contains("navigation_classroom_not_found_total{building=\"УК3\"} 1\n"));
+        assert!(body.contains("navigation_store_operation_duration_seconds_count{operation=\"get_classroom\"} 2\n"));
+        assert!(body.contains("navigation_dataset_classrooms 1\n"));
+        assert!(body.contains("navigation_store_up 1\n"));
+    }
+}
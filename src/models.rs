@@ -0,0 +1,67 @@
+use super::error::StoreError;
+use super::location::ClassroomLocation;
+
+/// Навигационные данные аудитории
+#[derive(Debug, serde::Serialize, serde::Deserialize, utoipa::ToSchema, Clone)]
+pub struct ClassroomData{
+    pub classroom: String,
+    pub images: Vec<String>,
+    pub description: String,
+    /// Разобранное имя аудитории, заполняется при импорте
+    #[serde(default)]
+    pub location: ClassroomLocation,
+    /// Где аудитория на плане этажа, если план для нее размечен
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub plan: Option<RoomPlan>,
+}
+
+/// Разметка аудитории на плане этажа
+#[derive(Debug, serde::Serialize, serde::Deserialize, utoipa::ToSchema, Clone, PartialEq)]
+pub struct RoomPlan{
+    /// id карты из maps.json, если план нельзя найти по корпусу и этажу аудитории
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub map: Option<String>,
+    /// Пиксели плана [x, y]: одна точка - метка, две - углы прямоугольника,
+    /// три и больше - контур аудитории
+    pub points: Vec<[u32; 2]>,
+}
+
+/// Картинка корпуса, закодированная в Base64
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone)]
+pub struct CampusImage{
+    pub name: String,
+    pub value: String,
+}
+
+/// Название картинки, ссылающееся на ее содержимое по SHA-256
+///
+/// # Примечание:
+/// Так картинки хранятся в DBClient: одно содержимое может иметь много названий
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq)]
+pub struct ImageAlias{
+    pub name: String,
+    pub hash: String,
+}
+
+/// Разбирает навигационные данные аудиторий из JSON-строки
+///
+/// # Примечание:
+/// Местоположение каждой аудитории выводится из ее имени, даже если оно было в JSON.
+/// Если JSON не подходит под схему, выдает ошибку StoreError::InvalidData
+pub fn parse_classroom_data(data: &str) -> Result<Vec<ClassroomData>, StoreError> {
+    let mut classrooms: Vec<ClassroomData> = serde_json::from_str(data)
+        .map_err(|e|StoreError::InvalidData(format!("classrooms: {}", e)))?;
+    for classroom in classrooms.iter_mut() {
+        classroom.location = ClassroomLocation::parse(&classroom.classroom);
+    }
+    Ok(classrooms)
+}
+
+/// Разбирает картинки корпусов из JSON-строки
+///
+/// # Примечание:
+/// Содержимое картинок не раскодируется. Если JSON не подходит под схему,
+/// выдает ошибку StoreError::InvalidData
+pub fn parse_image_data(data: &str) -> Result<Vec<CampusImage>, StoreError> {
+    serde_json::from_str(data).map_err(|e|StoreError::InvalidData(format!("images: {}", e)))
+}
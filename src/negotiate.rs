@@ -0,0 +1,380 @@
+use actix_web::body::{self, BoxBody, MessageBody};
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse};
+use actix_web::http::header::{self, HeaderMap, HeaderValue};
+use actix_web::http::StatusCode;
+use actix_web::{HttpRequest, ResponseError};
+use serde_json::{json, Value};
+use std::fmt::Write;
+use std::future::Future;
+use super::error::ApiError;
+
+/// Content-Type JSON-ответов
+pub const JSON_CONTENT_TYPE: &str = "application/json; charset=utf-8";
+/// Content-Type ответов для чтения человеком
+pub const TEXT_CONTENT_TYPE: &str = "text/plain; charset=utf-8";
+/// Content-Type ответов в MessagePack
+pub const MSGPACK_CONTENT_TYPE: &str = "application/msgpack";
+/// Строки длиннее этого (обычно картинки в Base64) в текстовом ответе обрезаются
+const MAX_TEXT_LEN: usize = 120;
+/// Маршруты, которые отдают не JSON: для них Accept проверяется только по ответу
+const RAW_ROUTES: [&str; 7] = [
+    "/images/{name}",
+    "/api/v1/images/{name}",
+    "/classroom/plan",
+    "/api/v1/classrooms/{name}/plan",
+    "/api/v1/docs/{_:.*}",
+    "/metrics",
+    "/test",
+];
+
+/// В каком формате отдавать данные, выбирается по заголовку Accept
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format{
+    Json,
+    /// Отступы вместо скобок, для отладки в браузере и curl
+    Text,
+    /// Компактный двоичный формат для бота
+    MsgPack,
+}
+
+impl Format{
+    /// Форматы в порядке предпочтения, если клиенту подходят несколько
+    const ALL: [Format; 3] = [Format::Json, Format::MsgPack, Format::Text];
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Json => JSON_CONTENT_TYPE,
+            Self::Text => TEXT_CONTENT_TYPE,
+            Self::MsgPack => MSGPACK_CONTENT_TYPE,
+        }
+    }
+
+    fn media_types(self) -> &'static [&'static str] {
+        match self {
+            Self::Json => &["application/json"],
+            Self::Text => &["text/plain"],
+            Self::MsgPack => &["application/msgpack", "application/x-msgpack"],
+        }
+    }
+
+    fn wildcard(self) -> &'static str {
+        match self {
+            Self::Json | Self::MsgPack => "application/*",
+            Self::Text => "text/*",
+        }
+    }
+
+    /// Выбирает формат по заголовку Accept
+    ///
+    /// # Примечание:
+    /// Без заголовка или с */* выбирается JSON. Точный тип важнее маски (text/*, */*),
+    /// при равном q побеждает JSON, потом MessagePack. None - клиенту не подходит ни один формат
+    pub fn from_accept(accept: Option<&str>) -> Option<Self> {
+        let ranges: Vec<(String, f32)> = accept
+            .unwrap_or_default()
+            .split(',')
+            .filter_map(|range|{
+                let mut params = range.split(';');
+                let media_type = params.next()?.trim().to_ascii_lowercase();
+                let quality = params
+                    .find_map(|param|param.trim().strip_prefix("q="))
+                    .map(|q|q.trim().parse::<f32>().unwrap_or(0.0))
+                    .unwrap_or(1.0);
+                (!media_type.is_empty()).then_some((media_type, quality))
+            })
+            .collect();
+        if ranges.is_empty() {
+            return Some(Self::Json);
+        }
+        let quality = |matches: &dyn Fn(&str) -> bool|{
+            ranges.iter()
+                .filter(|(media_type, _)|matches(media_type))
+                .map(|(_, quality)|*quality)
+                .reduce(f32::max)
+        };
+        let mut best: Option<(Self, f32)> = None;
+        for format in Self::ALL {
+            let exact = quality(&|media_type|format.media_types().contains(&media_type));
+            let wildcard = ||quality(&|media_type|media_type == "*/*" || media_type == format.wildcard());
+            let q = exact.or_else(wildcard).unwrap_or(0.0);
+            if q > 0.0 && best.is_none_or(|(_, best_q)|q > best_q) {
+                best = Some((format, q));
+            }
+        }
+        best.map(|(format, _)|format)
+    }
+
+    /// Перекодирует JSON-ответ в этот формат
+    fn encode(self, json: &[u8]) -> Result<Vec<u8>, String> {
+        if self == Self::Json {
+            return Ok(json.to_vec());
+        }
+        let value: Value = serde_json::from_slice(json).map_err(|e|e.to_string())?;
+        match self {
+            Self::Json => unreachable!(),
+            Self::Text => Ok(render_text(&value).into_bytes()),
+            Self::MsgPack => rmp_serde::to_vec_named(&value).map_err(|e|e.to_string()),
+        }
+    }
+}
+
+/// Выводит JSON-значение строками "ключ: значение" с отступами
+///
+/// # Примечание:
+/// Длинные строки (картинки в Base64) обрезаются: текстовый формат только для отладки
+pub fn render_text(value: &Value) -> String {
+    let mut out = String::new();
+    write_text(&mut out, value, 0);
+    out
+}
+
+fn write_text(out: &mut String, value: &Value, indent: usize) {
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            for (key, value) in map {
+                match is_nested(value) {
+                    true => {
+                        let _ = writeln!(out, "{:indent$}{}:", "", key);
+                        write_text(out, value, indent + 2);
+                    },
+                    false => {
+                        let _ = writeln!(out, "{:indent$}{}: {}", "", key, scalar(value));
+                    },
+                }
+            }
+        },
+        Value::Array(items) if !items.is_empty() => {
+            for item in items {
+                match is_nested(item) {
+                    true => {
+                        let _ = writeln!(out, "{:indent$}-", "");
+                        write_text(out, item, indent + 2);
+                    },
+                    false => {
+                        let _ = writeln!(out, "{:indent$}- {}", "", scalar(item));
+                    },
+                }
+            }
+        },
+        _ => {
+            let _ = writeln!(out, "{:indent$}{}", "", scalar(value));
+        },
+    }
+}
+
+fn is_nested(value: &Value) -> bool {
+    match value {
+        Value::Object(map) => !map.is_empty(),
+        Value::Array(items) => !items.is_empty(),
+        _ => false,
+    }
+}
+
+fn scalar(value: &Value) -> String {
+    match value {
+        Value::String(text) if text.chars().count() > MAX_TEXT_LEN => {
+            let head: String = text.chars().take(MAX_TEXT_LEN / 2).collect();
+            format!("{}... ({} chars)", head, text.chars().count())
+        },
+        Value::String(text) => text.to_owned(),
+        Value::Object(_) => "{}".to_owned(),
+        Value::Array(_) => "[]".to_owned(),
+        other => other.to_string(),
+    }
+}
+
+/// Отдается ли в ответе JSON, который можно перекодировать
+fn is_json(headers: &HeaderMap) -> bool {
+    headers.get(header::CONTENT_TYPE)
+        .and_then(|value|value.to_str().ok())
+        .is_some_and(|value|value.trim_start().to_ascii_lowercase().starts_with("application/json"))
+}
+
+/// Отдает ли маршрут запроса не JSON (картинки, PNG-планы, метрики)
+fn is_raw_route(request: &ServiceRequest) -> bool {
+    request.match_pattern().is_some_and(|pattern|RAW_ROUTES.contains(&pattern.as_str()))
+}
+
+fn not_acceptable() -> ApiError {
+    let supported: Vec<&str> = Format::ALL.iter().map(|x|x.media_types()[0]).collect();
+    ApiError::new(StatusCode::NOT_ACCEPTABLE, "not_acceptable", "None of the types in Accept is supported")
+        .with_details(json!({"supported": supported}))
+}
+
+/// Ответ с ошибкой вместо ответа обработчика, в JSON
+fn error_response(request: HttpRequest, error: ApiError) -> ServiceResponse<BoxBody> {
+    let mut response = error.error_response();
+    response.headers_mut().insert(header::CONTENT_TYPE, HeaderValue::from_static(JSON_CONTENT_TYPE));
+    ServiceResponse::new(request, response)
+}
+
+/// Middleware для App::wrap_fn: отдает JSON-ответы в формате из заголовка Accept
+///
+/// # Что делает?
+/// - Ставит JSON-ответам Content-Type "application/json; charset=utf-8" и Vary: Accept
+/// - Перекодирует их в MessagePack или текст, если клиент просит application/msgpack или text/plain
+/// - Отвечает 406 not_acceptable, если клиенту не подходит ни один из этих форматов
+///
+/// # Примечание:
+/// Обработчики пишут только JSON. Картинки, метрики и прочие не-JSON ответы не трогаются.
+/// 406 отдается до вызова обработчика, чтобы правка не применилась с ответом "не могу ответить",
+/// кроме маршрутов из RAW_ROUTES: там Accept проверяется, только если ответ оказался JSON (ошибка)
+pub fn negotiate<S, B>(request: ServiceRequest, service: &S) -> impl Future<Output = Result<ServiceResponse<BoxBody>, actix_web::Error>>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody + 'static,
+{
+    let format = Format::from_accept(request.headers().get(header::ACCEPT).and_then(|x|x.to_str().ok()));
+    let call = match format.is_some() || is_raw_route(&request) {
+        true => Ok(service.call(request)),
+        false => Err(request.into_parts().0),
+    };
+    async move {
+        let response = match call {
+            Ok(response) => response.await?,
+            Err(request) => return Ok(error_response(request, not_acceptable())),
+        };
+        if !is_json(response.headers()) {
+            return Ok(response.map_into_boxed_body());
+        }
+        let (request, response) = response.into_parts();
+        let format = match format {
+            Some(val) => val,
+            None => return Ok(error_response(request, not_acceptable())),
+        };
+        let (mut head, body) = response.into_parts();
+        let bytes = match body::to_bytes(body).await {
+            Ok(val) => val,
+            Err(e) => return Ok(error_response(request, ApiError::internal(e.into().to_string()))),
+        };
+        let encoded = match format.encode(&bytes) {
+            Ok(val) => val,
+            Err(e) => return Ok(error_response(request, ApiError::internal(format!("response not encoded: {}", e)))),
+        };
+        head.headers_mut().insert(header::CONTENT_TYPE, HeaderValue::from_static(format.content_type()));
+        head.headers_mut().append(header::VARY, HeaderValue::from_static("accept"));
+        Ok(ServiceResponse::new(request, head.set_body(BoxBody::new(encoded))))
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use serial_test::serial;
+    use actix_service::Service as _;
+    use actix_web::test::TestRequest;
+    use super::*;
+    use super::super::server::{Server, StorageKind};
+
+    #[test]
+    fn test_accept_parse(){
+        let cases = [
+            (None, Some(Format::Json)),
+            (Some(""), Some(Format::Json)),
+            (Some("*/*"), Some(Format::Json)),
+            (Some("application/json"), Some(Format::Json)),
+            (Some("text/plain"), Some(Format::Text)),
+            (Some("Application/MsgPack"), Some(Format::MsgPack)),
+            (Some("application/x-msgpack"), Some(Format::MsgPack)),
+            (Some("application/json;q=0.5, application/msgpack"), Some(Format::MsgPack)),
+            (Some("text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8"), Some(Format::Json)),
+            (Some("*/*;q=0.1, text/plain"), Some(Format::Text)),
+            (Some("text/*"), Some(Format::Text)),
+            (Some("application/json;q=0, */*"), Some(Format::MsgPack)),
+            (Some("application/xml"), None),
+            (Some("text/plain;q=0"), None),
+        ];
+        for (accept, format) in cases {
+            assert_eq!(Format::from_accept(accept), format, "{:?}", accept);
+        }
+    }
+
+    #[test]
+    fn test_render_text(){
+        let value = json!({
+            "classroom": "УК3 104",
+            "images": ["x".repeat(200)],
+            "location": {"floor": 1, "wing": null},
+            "plan": {},
+        });
+        let text = render_text(&value);
+        let expected = format!("classroom: УК3 104\nimages:\n  - {}... (200 chars)\nlocation:\n  floor: 1\n  wing: null\nplan: {{}}\n", "x".repeat(60));
+        assert_eq!(text, expected);
+    }
+
+    #[actix_web::test]
+    #[serial]
+    async fn test_negotiation(){
+        let app = Server::builder()
+            .classroom_data(json!([{"classroom": "УК3 104", "description": "", "images": []}]).to_string())
+            .storage(StorageKind::Memory)
+            .build()
+            .test_start()
+            .await
+            .unwrap();
+
+        for uri in ["/classroomlist", "/api/v1/classrooms", "/readyz"] {
+            let res = app.call(TestRequest::with_uri(uri).to_request()).await.unwrap();
+            assert_eq!(res.headers().get(header::CONTENT_TYPE).unwrap(), JSON_CONTENT_TYPE, "{}", uri);
+        }
+
+        let res = app.call(TestRequest::with_uri("/api/v1/classrooms").insert_header((header::ACCEPT, "application/msgpack")).to_request()).await.unwrap();
+        assert_eq!(res.headers().get(header::CONTENT_TYPE).unwrap(), MSGPACK_CONTENT_TYPE);
+        assert_eq!(res.headers().get(header::VARY).unwrap(), "accept");
+        let body = actix_web::test::read_body(res).await;
+        let value: Value = rmp_serde::from_slice(&body).unwrap();
+        assert_eq!(value, json!({"classrooms": ["УК3 104"]}));
+
+        let res = app.call(TestRequest::with_uri("/classroomlist").insert_header((header::ACCEPT, "text/plain")).to_request()).await.unwrap();
+        assert_eq!(res.headers().get(header::CONTENT_TYPE).unwrap(), TEXT_CONTENT_TYPE);
+        assert_eq!(actix_web::test::read_body(res).await, "- УК3 104\n");
+
+        let uri = format!("/api/v1/classrooms/{}", urlencoding::encode("УК3 999"));
+        let res = app.call(TestRequest::with_uri(&uri).insert_header((header::ACCEPT, "application/msgpack")).to_request()).await.unwrap();
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        let value: Value = rmp_serde::from_slice(&actix_web::test::read_body(res).await).unwrap();
+        assert_eq!(value["code"], "classroom_not_found");
+
+        let res = app.call(TestRequest::with_uri("/api/v1/classrooms").insert_header((header::ACCEPT, "application/xml")).to_request()).await.unwrap();
+        assert_eq!(res.status(), StatusCode::NOT_ACCEPTABLE);
+        assert_eq!(res.headers().get(header::CONTENT_TYPE).unwrap(), JSON_CONTENT_TYPE);
+        let res_body: Value = actix_web::test::read_body_json(res).await;
+        assert_eq!(res_body["code"], "not_acceptable");
+
+        let res = app.call(TestRequest::with_uri("/metrics").insert_header((header::ACCEPT, "application/msgpack")).to_request()).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert!(res.headers().get(header::CONTENT_TYPE).unwrap().to_str().unwrap().starts_with("text/plain; version=0.0.4"));
+    }
+
+    #[actix_web::test]
+    #[serial]
+    async fn test_not_acceptable_before_handler(){
+        let app = Server::builder()
+            .classroom_data(json!([{"classroom": "УК3 104", "description": "", "images": ["UK3-left.png"]}]).to_string())
+            .image_data(json!([{"name": "UK3-left.png", "value": "bibabob0"}]).to_string())
+            .storage(StorageKind::Memory)
+            .admin_token("secret".to_owned())
+            .build()
+            .test_start()
+            .await
+            .unwrap();
+
+        let classroom = json!({"classroom": "УК3 105", "description": "", "images": []});
+        let req = TestRequest::post()
+            .uri("/admin/classrooms")
+            .insert_header(("Authorization", "Bearer secret"))
+            .insert_header((header::ACCEPT, "application/xml"))
+            .set_json(&classroom)
+            .to_request();
+        let res = app.call(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::NOT_ACCEPTABLE);
+        let res = app.call(TestRequest::with_uri("/api/v1/classrooms").to_request()).await.unwrap();
+        let res_body: Value = actix_web::test::read_body_json(res).await;
+        assert_eq!(res_body, json!({"classrooms": ["УК3 104"]}));
+
+        let res = app.call(TestRequest::with_uri("/images/UK3-left.png").insert_header((header::ACCEPT, "image/png")).to_request()).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers().get(header::CONTENT_TYPE).unwrap(), "image/png");
+        let res = app.call(TestRequest::with_uri("/images/UK3-back.png").insert_header((header::ACCEPT, "image/png")).to_request()).await.unwrap();
+        assert_eq!(res.status(), StatusCode::NOT_ACCEPTABLE);
+    }
+}
@@ -0,0 +1,202 @@
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+use super::server::SharedStore;
+
+/// Пути к файлам с навигационными данными
+#[derive(Debug, Clone)]
+pub struct DataFiles{
+    pub classrooms: PathBuf,
+    pub images: PathBuf,
+}
+
+/// Отметка о состоянии файла: время изменения и размер
+type FileStamp = Option<(SystemTime, u64)>;
+
+/// Результат последней перезагрузки данных
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, Default)]
+pub struct ReloadStatus{
+    /// Сколько раз данные успешно перезагружались с момента запуска
+    pub reloads: u64,
+    /// Ошибка последней попытки, если она не удалась
+    pub last_error: Option<String>,
+}
+
+/// Перезагрузчик навигационных данных
+///
+/// # Что делает?
+/// - Следит за изменением classrooms.json и images.json
+/// - Перезагружает данные по SIGHUP и по запросу /admin/reload
+/// - Если новые данные не разбираются, хранилище продолжает отдавать старые
+pub struct Reloader{
+    files: DataFiles,
+    store: SharedStore,
+    stamps: Mutex<(FileStamp, FileStamp)>,
+    status: Mutex<ReloadStatus>,
+}
+
+impl Reloader{
+    /// Создает перезагрузчик
+    ///
+    /// # Примечание:
+    /// Текущее состояние файлов считается уже загруженным
+    pub fn new(files: DataFiles, store: SharedStore) -> Self {
+        let stamps = (stamp(&files.classrooms), stamp(&files.images));
+        Self{
+            files,
+            store,
+            stamps: Mutex::new(stamps),
+            status: Mutex::new(ReloadStatus::default())}
+    }
+
+    /// Перечитывает файлы и подменяет данные в хранилище
+    pub async fn reload(&self) -> Result<ReloadStatus, Box<dyn Error>> {
+        let result = self.read_and_swap().await;
+        let mut status = self.status.lock().unwrap();
+        match &result {
+            Ok(_) => {
+                status.reloads += 1;
+                status.last_error = None;
+                tracing::info!(reloads = status.reloads, "navigation data reloaded");
+            },
+            Err(e) => status.last_error = Some(e.to_string()),
+        }
+        result.map(|_|status.clone())
+    }
+
+    /// Выдает результат последней перезагрузки
+    pub fn status(&self) -> ReloadStatus {
+        self.status.lock().unwrap().clone()
+    }
+
+    /// Перезагружает данные, если файлы изменились с прошлой проверки
+    ///
+    /// # Примечание:
+    /// Выдает true, если была попытка перезагрузки
+    pub async fn reload_if_changed(&self) -> bool {
+        let current = (stamp(&self.files.classrooms), stamp(&self.files.images));
+        {
+            let mut stamps = self.stamps.lock().unwrap();
+            if *stamps == current {
+                return false;
+            }
+            *stamps = current;
+        }
+        if let Err(e) = self.reload().await {
+            tracing::error!(error = %e, "navigation data reload failed, keeping the previous dataset");
+        }
+        true
+    }
+
+    /// Следит за файлами и сигналом SIGHUP, не завершается
+    ///
+    /// # Аргументы:
+    /// - interval: как часто проверять файлы
+    pub async fn run(&self, interval: Duration) {
+        futures::future::join(self.watch(interval), self.listen_hangup()).await;
+    }
+
+    async fn watch(&self, interval: Duration) {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            self.reload_if_changed().await;
+        }
+    }
+
+    #[cfg(unix)]
+    async fn listen_hangup(&self) {
+        use tokio::signal::unix::{signal, SignalKind};
+        let mut hangup = match signal(SignalKind::hangup()) {
+            Ok(val) => val,
+            Err(e) => {
+                tracing::warn!(error = %e, "SIGHUP handler not installed");
+                return futures::future::pending().await;
+            },
+        };
+        while hangup.recv().await.is_some() {
+            if let Err(e) = self.reload().await {
+                tracing::error!(error = %e, "navigation data reload failed, keeping the previous dataset");
+            }
+        }
+    }
+
+    #[cfg(not(unix))]
+    async fn listen_hangup(&self) {
+        futures::future::pending().await
+    }
+
+    async fn read_and_swap(&self) -> Result<(), Box<dyn Error>> {
+        let classroom_data = std::fs::read_to_string(&self.files.classrooms)?;
+        let image_data = std::fs::read_to_string(&self.files.images)?;
+        Ok(self.store.reload(classroom_data, image_data).await?)
+    }
+}
+
+fn stamp(path: &Path) -> FileStamp {
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+#[cfg(test)]
+mod tests{
+    use actix_web::web;
+    use serde_json::json;
+    use std::sync::Arc;
+    use super::*;
+    use super::super::memory_store::MemoryStore;
+    use super::super::store::NavigationStore;
+
+    fn classroom_data(name: &str) -> String {
+        json!([{"classroom": name, "description": "", "images": []}]).to_string()
+    }
+
+    fn temp_files(test_name: &str) -> DataFiles {
+        let dir = std::env::temp_dir().join(format!("nav_reload_{}_{}", test_name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        DataFiles{classrooms: dir.join("classrooms.json"), images: dir.join("images.json")}
+    }
+
+    fn reloader(files: &DataFiles) -> Reloader {
+        std::fs::write(&files.classrooms, classroom_data("УК3 104")).unwrap();
+        std::fs::write(&files.images, "[]").unwrap();
+        let store: Arc<dyn NavigationStore> = Arc::new(MemoryStore::new(classroom_data("УК3 104"), "[]".to_owned()).unwrap());
+        Reloader::new(files.clone(), web::Data::from(store))
+    }
+
+    async fn names(reloader: &Reloader) -> Vec<String> {
+        reloader.store.get_classroom_names().await.unwrap()
+    }
+
+    #[actix_web::test]
+    async fn test_reload_ok(){
+        let files = temp_files("ok");
+        let reloader = reloader(&files);
+        std::fs::write(&files.classrooms, classroom_data("ГУК 305")).unwrap();
+        let status = reloader.reload().await.unwrap();
+        assert_eq!(status.reloads, 1);
+        assert_eq!(names(&reloader).await, vec!["ГУК 305"]);
+    }
+
+    #[actix_web::test]
+    async fn test_reload_keeps_previous_on_error(){
+        let files = temp_files("error");
+        let reloader = reloader(&files);
+        std::fs::write(&files.classrooms, "[{\"classroom\": ").unwrap();
+        assert!(reloader.reload().await.is_err());
+        assert!(reloader.status().last_error.is_some());
+        assert_eq!(names(&reloader).await, vec!["УК3 104"]);
+    }
+
+    #[actix_web::test]
+    async fn test_reload_if_changed(){
+        let files = temp_files("changed");
+        let reloader = reloader(&files);
+        assert!(!reloader.reload_if_changed().await);
+        std::fs::write(&files.classrooms, classroom_data("КБ 102а")).unwrap();
+        assert!(reloader.reload_if_changed().await);
+        assert_eq!(names(&reloader).await, vec!["КБ 102а"]);
+        assert!(!reloader.reload_if_changed().await);
+    }
+}
@@ -0,0 +1,389 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::error::Error;
+use super::location::ClassroomLocation;
+use super::models::ClassroomData;
+
+/// Тип узла схемы кампуса
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum NodeKind{
+    /// Территория кампуса между корпусами
+    Yard,
+    /// Вход в корпус
+    Entrance,
+    /// Коридор этажа, к нему привязываются аудитории
+    Corridor,
+    /// Лестница на конкретном этаже
+    Stairwell,
+    /// Переход между корпусами
+    Passage,
+}
+
+/// Узел схемы кампуса
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone)]
+pub struct GraphNode{
+    pub id: String,
+    pub kind: NodeKind,
+    #[serde(default)]
+    pub building: Option<String>,
+    #[serde(default)]
+    pub floor: Option<u32>,
+    /// Человекочитаемое название в именительном падеже: "главный вход УК3", "коридор 3 этажа ГУК"
+    pub name: String,
+    /// Названия картинок (CampusImage), по которым узел легко узнать
+    #[serde(default)]
+    pub images: Vec<String>,
+}
+
+/// Ребро схемы кампуса, проходимо в обе стороны
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone)]
+pub struct GraphEdge{
+    pub from: String,
+    pub to: String,
+    /// Условная длина пути, например в метрах
+    pub distance: u32,
+    /// Своя подсказка для шага вместо сгенерированной
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct GraphData{
+    nodes: Vec<GraphNode>,
+    edges: Vec<GraphEdge>,
+}
+
+/// Шаг маршрута
+#[derive(Debug, serde::Serialize, serde::Deserialize, utoipa::ToSchema, Clone, PartialEq)]
+pub struct RouteStep{
+    pub instruction: String,
+    pub images: Vec<String>,
+}
+
+/// Маршрут между двумя аудиториями
+#[derive(Debug, serde::Serialize, serde::Deserialize, utoipa::ToSchema, Clone, PartialEq)]
+pub struct Route{
+    pub from: String,
+    pub to: String,
+    pub distance: u32,
+    pub steps: Vec<RouteStep>,
+}
+
+/// Схема кампуса: корпуса, входы, лестницы, коридоры и переходы
+///
+/// # Что делает?
+/// - Загружается из campus_graph.json
+/// - Ищет кратчайший путь между аудиториями (алгоритм Дейкстры)
+/// - Превращает путь в список понятных человеку шагов
+#[derive(Debug, Default)]
+pub struct CampusGraph{
+    nodes: Vec<GraphNode>,
+    edges: Vec<GraphEdge>,
+    /// Для каждого узла: (соседний узел, индекс ребра)
+    adjacency: Vec<Vec<(usize, usize)>>,
+}
+
+impl CampusGraph{
+    /// Загружает схему кампуса из JSON-строки
+    ///
+    /// # Аргументы:
+    /// - data: схема в виде {nodes: [GraphNode], edges: [GraphEdge]}
+    ///
+    /// # Примечание:
+    /// Выдает ошибку на повторяющиеся id узлов и ребра к несуществующим узлам
+    pub fn from_json(data: &str) -> Result<Self, Box<dyn Error>> {
+        let data: GraphData = serde_json::from_str(data)?;
+        let mut index: HashMap<&str, usize> = HashMap::new();
+        for (idx, node) in data.nodes.iter().enumerate() {
+            if index.insert(&node.id, idx).is_some() {
+                return Err(format!("Duplicate graph node: {}", node.id).into());
+            }
+        }
+
+        let mut adjacency = vec![Vec::new(); data.nodes.len()];
+        for (edge_idx, edge) in data.edges.iter().enumerate() {
+            let from = *index.get(edge.from.as_str()).ok_or(format!("Unknown graph node: {}", edge.from))?;
+            let to = *index.get(edge.to.as_str()).ok_or(format!("Unknown graph node: {}", edge.to))?;
+            adjacency[from].push((to, edge_idx));
+            adjacency[to].push((from, edge_idx));
+        }
+        Ok(Self{nodes: data.nodes, edges: data.edges, adjacency})
+    }
+
+    /// Строит маршрут между двумя аудиториями
+    ///
+    /// # Аргументы:
+    /// - from: аудитория, откуда идти
+    /// - to: аудитория, куда идти
+    ///
+    /// # Примечание:
+    /// Аудитория привязывается к коридору своего корпуса и этажа,
+    /// если этаж не понятен из имени - к коридору 1 этажа
+    pub fn plan_route(&self, from: &ClassroomData, to: &ClassroomData) -> Result<Route, Box<dyn Error>> {
+        let start = self.locate(from)?;
+        let finish = self.locate(to)?;
+        let (distance, path, edges) = self.shortest_path(start, finish)
+            .ok_or(format!("No route between {} and {}", from.classroom, to.classroom))?;
+
+        let mut steps = vec![RouteStep{
+            instruction: format!("Выйдите из аудитории {} в коридор", from.classroom),
+            images: Vec::new()}];
+        steps.extend(self.describe(&path, &edges));
+        let instruction = match to.location.floor {
+            Some(floor) => format!("Найдите аудиторию {} {}", to.classroom, floor_name(floor, "на", "этаже")),
+            None => format!("Найдите аудиторию {}", to.classroom),
+        };
+        steps.push(RouteStep{instruction, images: to.images.clone()});
+
+        Ok(Route{
+            from: from.classroom.to_owned(),
+            to: to.classroom.to_owned(),
+            distance,
+            steps})
+    }
+
+    /// Находит коридор, к которому относится аудитория
+    fn locate(&self, classroom: &ClassroomData) -> Result<usize, Box<dyn Error>> {
+        let ClassroomLocation{building, floor, ..} = &classroom.location;
+        let floor = floor.unwrap_or(1);
+        self.nodes
+            .iter()
+            .position(|node|{
+                node.kind == NodeKind::Corridor
+                    && node.building.is_some()
+                    && node.building == *building
+                    && node.floor == Some(floor)})
+            .ok_or(format!("Classroom {} is not on the campus graph", classroom.classroom).into())
+    }
+
+    /// Алгоритм Дейкстры
+    ///
+    /// # Примечание:
+    /// Выдает длину пути, узлы пути и ребра между ними (на одно меньше, чем узлов)
+    fn shortest_path(&self, start: usize, finish: usize) -> Option<(u32, Vec<usize>, Vec<usize>)> {
+        let mut distances = vec![u32::MAX; self.nodes.len()];
+        let mut previous: Vec<Option<(usize, usize)>> = vec![None; self.nodes.len()];
+        let mut queue = BinaryHeap::new();
+        distances[start] = 0;
+        queue.push(Reverse((0, start)));
+
+        while let Some(Reverse((distance, node))) = queue.pop() {
+            if node == finish {
+                break;
+            }
+            if distance > distances[node] {
+                continue;
+            }
+            for &(next, edge_idx) in &self.adjacency[node] {
+                let next_distance = distance.saturating_add(self.edges[edge_idx].distance);
+                if next_distance < distances[next] {
+                    distances[next] = next_distance;
+                    previous[next] = Some((node, edge_idx));
+                    queue.push(Reverse((next_distance, next)));
+                }
+            }
+        }
+        if distances[finish] == u32::MAX {
+            return None;
+        }
+
+        let mut path = vec![finish];
+        let mut edges = Vec::new();
+        while let Some((node, edge_idx)) = previous[*path.last().unwrap()] {
+            path.push(node);
+            edges.push(edge_idx);
+        }
+        path.reverse();
+        edges.reverse();
+        Some((distances[finish], path, edges))
+    }
+
+    /// Превращает путь по схеме в шаги маршрута
+    fn describe(&self, path: &[usize], edges: &[usize]) -> Vec<RouteStep> {
+        let mut steps = Vec::new();
+        let mut idx = 0;
+        while idx + 1 < path.len() {
+            let (current, next) = (&self.nodes[path[idx]], &self.nodes[path[idx + 1]]);
+            if let Some(description) = &self.edges[edges[idx]].description {
+                steps.push(RouteStep{instruction: description.to_owned(), images: next.images.clone()});
+                idx += 1;
+                continue;
+            }
+
+            let building = next.building.as_deref().unwrap_or_default();
+            let instruction = match (current.kind, next.kind) {
+                (NodeKind::Stairwell, NodeKind::Stairwell) => {
+                    let mut last = idx + 1;
+                    while last + 1 < path.len()
+                        && self.nodes[path[last + 1]].kind == NodeKind::Stairwell
+                        && self.edges[edges[last]].description.is_none() {
+                        last += 1;
+                    }
+                    let target = &self.nodes[path[last]];
+                    let (from_floor, to_floor) = (current.floor.unwrap_or_default(), target.floor.unwrap_or_default());
+                    let direction = if to_floor > from_floor {"Поднимитесь"} else {"Спуститесь"};
+                    steps.push(RouteStep{
+                        instruction: format!("{} по лестнице {}", direction, floor_name(to_floor, "на", "этаж")),
+                        images: target.images.clone()});
+                    idx = last;
+                    continue;
+                },
+                (_, NodeKind::Stairwell) => "Дойдите до лестницы".to_owned(),
+                (NodeKind::Stairwell, _) => "Выйдите с лестницы в коридор".to_owned(),
+                (_, NodeKind::Entrance) if current.kind != NodeKind::Yard => {
+                    format!("Выйдите из корпуса {} ({})", current.building.as_deref().unwrap_or_default(), next.name)
+                },
+                (NodeKind::Entrance, NodeKind::Yard) => {
+                    idx += 1;
+                    continue;
+                },
+                (NodeKind::Yard, _) => format!("Пройдите по территории кампуса к корпусу {} ({})", building, next.name),
+                (NodeKind::Entrance, _) => format!("Войдите в корпус {}", building),
+                (_, NodeKind::Passage) => {
+                    idx += 1;
+                    continue;
+                },
+                (NodeKind::Passage, _) => {
+                    let mut images = current.images.clone();
+                    images.extend(next.images.iter().cloned());
+                    steps.push(RouteStep{
+                        instruction: format!("Пройдите по переходу в корпус {} ({})", building, current.name),
+                        images});
+                    idx += 1;
+                    continue;
+                },
+                _ => format!("Пройдите дальше ({})", next.name),
+            };
+            steps.push(RouteStep{instruction, images: next.images.clone()});
+            idx += 1;
+        }
+        steps
+    }
+}
+
+/// Название этажа с предлогом: "на 3 этаж", "на цокольном этаже"
+fn floor_name(floor: u32, preposition: &str, noun: &str) -> String {
+    if floor == 0 {
+        let adjective = if noun == "этаж" {"цокольный"} else {"цокольном"};
+        return format!("{} {} {}", preposition, adjective, noun);
+    }
+    format!("{} {} {}", preposition, floor, noun)
+}
+
+#[cfg(test)]
+mod tests{
+    use serde_json::json;
+    use super::*;
+
+    fn valid_graph_data() -> String {
+        json!({
+            "nodes": [
+                {"id": "yard", "kind": "yard", "name": "территория кампуса"},
+                {"id": "uk3-entrance", "kind": "entrance", "building": "УК3", "floor": 1, "name": "главный вход УК3", "images": ["UK3-front.png"]},
+                {"id": "uk3-f1", "kind": "corridor", "building": "УК3", "floor": 1, "name": "коридор 1 этажа УК3"},
+                {"id": "uk3-f1-stairs", "kind": "stairwell", "building": "УК3", "floor": 1, "name": "лестница УК3"},
+                {"id": "uk3-f2-stairs", "kind": "stairwell", "building": "УК3", "floor": 2, "name": "лестница УК3"},
+                {"id": "uk3-f3-stairs", "kind": "stairwell", "building": "УК3", "floor": 3, "name": "лестница УК3"},
+                {"id": "uk3-f3", "kind": "corridor", "building": "УК3", "floor": 3, "name": "коридор 3 этажа УК3"},
+                {"id": "guk-entrance", "kind": "entrance", "building": "ГУК", "floor": 1, "name": "главный вход ГУК", "images": ["GUK-front.png"]},
+                {"id": "guk-f1", "kind": "corridor", "building": "ГУК", "floor": 1, "name": "коридор 1 этажа ГУК"},
+                {"id": "uk4-f1", "kind": "corridor", "building": "УК4", "floor": 1, "name": "коридор 1 этажа УК4"},
+                {"id": "uk3-uk4", "kind": "passage", "name": "переход УК3-УК4"},
+            ],
+            "edges": [
+                {"from": "yard", "to": "uk3-entrance", "distance": 100},
+                {"from": "yard", "to": "guk-entrance", "distance": 100},
+                {"from": "uk3-entrance", "to": "uk3-f1", "distance": 10},
+                {"from": "guk-entrance", "to": "guk-f1", "distance": 10},
+                {"from": "uk3-f1", "to": "uk3-f1-stairs", "distance": 20},
+                {"from": "uk3-f1-stairs", "to": "uk3-f2-stairs", "distance": 15},
+                {"from": "uk3-f2-stairs", "to": "uk3-f3-stairs", "distance": 15},
+                {"from": "uk3-f3-stairs", "to": "uk3-f3", "distance": 20},
+                {"from": "uk3-f1", "to": "uk3-uk4", "distance": 30},
+                {"from": "uk3-uk4", "to": "uk4-f1", "distance": 30},
+            ],
+        }).to_string()
+    }
+
+    fn classroom(name: &str, images: &[&str]) -> ClassroomData {
+        ClassroomData{
+            classroom: name.to_owned(),
+            images: images.iter().map(|x|x.to_string()).collect(),
+            description: String::new(),
+            location: ClassroomLocation::parse(name),
+            plan: None,
+        }
+    }
+
+    fn instructions(route: &Route) -> Vec<&str> {
+        route.steps.iter().map(|x|x.instruction.as_str()).collect()
+    }
+
+    #[test]
+    fn test_graph_bad(){
+        assert!(CampusGraph::from_json("{}").is_err());
+        let dangling = json!({"nodes": [], "edges": [{"from": "a", "to": "b", "distance": 1}]}).to_string();
+        assert!(CampusGraph::from_json(&dangling).is_err());
+        let duplicate = json!({"nodes": [
+            {"id": "a", "kind": "yard", "name": "a"},
+            {"id": "a", "kind": "yard", "name": "a"}], "edges": []}).to_string();
+        assert!(CampusGraph::from_json(&duplicate).is_err());
+    }
+
+    #[test]
+    fn test_route_between_buildings(){
+        let graph = CampusGraph::from_json(&valid_graph_data()).unwrap();
+        let route = graph.plan_route(&classroom("УК3 104", &[]), &classroom("ГУК 105", &["GUK-left.png"])).unwrap();
+        assert_eq!(route.distance, 220);
+        assert_eq!(instructions(&route), vec![
+            "Выйдите из аудитории УК3 104 в коридор",
+            "Выйдите из корпуса УК3 (главный вход УК3)",
+            "Пройдите по территории кампуса к корпусу ГУК (главный вход ГУК)",
+            "Войдите в корпус ГУК",
+            "Найдите аудиторию ГУК 105 на 1 этаже",
+        ]);
+        assert_eq!(route.steps[1].images, vec!["UK3-front.png"]);
+        assert_eq!(route.steps[4].images, vec!["GUK-left.png"]);
+    }
+
+    #[test]
+    fn test_route_stairs_collapsed(){
+        let graph = CampusGraph::from_json(&valid_graph_data()).unwrap();
+        let route = graph.plan_route(&classroom("УК3 104", &[]), &classroom("УК3 305", &[])).unwrap();
+        assert_eq!(instructions(&route), vec![
+            "Выйдите из аудитории УК3 104 в коридор",
+            "Дойдите до лестницы",
+            "Поднимитесь по лестнице на 3 этаж",
+            "Выйдите с лестницы в коридор",
+            "Найдите аудиторию УК3 305 на 3 этаже",
+        ]);
+    }
+
+    #[test]
+    fn test_route_through_passage(){
+        let graph = CampusGraph::from_json(&valid_graph_data()).unwrap();
+        let route = graph.plan_route(&classroom("УК3 104", &[]), &classroom("УК4 112", &[])).unwrap();
+        assert_eq!(route.distance, 60);
+        assert_eq!(route.steps[1].instruction, "Пройдите по переходу в корпус УК4 (переход УК3-УК4)");
+    }
+
+    #[test]
+    fn test_route_unknown(){
+        let graph = CampusGraph::from_json(&valid_graph_data()).unwrap();
+        assert!(graph.plan_route(&classroom("УК3 104", &[]), &classroom("Дист.", &[])).is_err());
+        assert!(graph.plan_route(&classroom("УК3 104", &[]), &classroom("УК3 504", &[])).is_err());
+    }
+
+    #[test]
+    fn test_shipped_graph_covers_classrooms(){
+        let graph = CampusGraph::from_json(include_str!("../campus_graph.json")).unwrap();
+        let classrooms = crate::models::parse_classroom_data(include_str!("../classrooms.json")).unwrap();
+        let start = classroom("ГУК 001", &[]);
+        for classroom in classrooms.iter().filter(|x|x.location.kind.is_room()) {
+            if let Err(e) = graph.plan_route(&start, classroom) {
+                panic!("{}: {:?}", classroom.classroom, e);
+            }
+        }
+    }
+}
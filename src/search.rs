@@ -0,0 +1,219 @@
+/// Минимальная оценка, при которой аудитория попадает в выдачу поиска
+const MIN_SCORE: f64 = 0.5;
+
+/// Латинские буквы, похожие на кириллические по написанию ("YK3" вместо "УК3")
+const LOOKALIKES: &[(char, char)] = &[
+    ('a', 'а'), ('b', 'в'), ('c', 'с'), ('e', 'е'), ('h', 'н'), ('k', 'к'), ('m', 'м'),
+    ('o', 'о'), ('p', 'р'), ('t', 'т'), ('x', 'х'), ('y', 'у'),
+];
+
+/// Транслитерация латиницы в кириллицу ("uk3" вместо "ук3"), длинные сочетания первыми
+const TRANSLITERATION: &[(&str, &str)] = &[
+    ("shch", "щ"), ("sch", "щ"), ("zh", "ж"), ("kh", "х"), ("ts", "ц"), ("ch", "ч"),
+    ("sh", "ш"), ("yu", "ю"), ("ya", "я"), ("yo", "е"),
+    ("a", "а"), ("b", "б"), ("c", "ц"), ("d", "д"), ("e", "е"), ("f", "ф"), ("g", "г"),
+    ("h", "х"), ("i", "и"), ("j", "й"), ("k", "к"), ("l", "л"), ("m", "м"), ("n", "н"),
+    ("o", "о"), ("p", "п"), ("q", "к"), ("r", "р"), ("s", "с"), ("t", "т"), ("u", "у"),
+    ("v", "в"), ("w", "в"), ("x", "кс"), ("y", "ы"), ("z", "з"),
+];
+
+/// Аудитория, найденная по запросу, с оценкой похожести от 0 до 1
+#[derive(Debug, serde::Serialize, serde::Deserialize, utoipa::ToSchema, Clone, PartialEq)]
+pub struct SearchCandidate{
+    pub classroom: String,
+    pub score: f64,
+}
+
+/// Ищет аудитории, похожие на запрос
+///
+/// # Аргументы:
+/// - query: запрос пользователя, например "uk3-104" или "104 УК3"
+/// - classroom_names: имена всех аудиторий
+/// - limit: сколько кандидатов выдать максимум
+///
+/// # Примечание:
+/// Кандидаты отсортированы по убыванию оценки, слабые совпадения отброшены
+pub fn search(query: &str, classroom_names: &[String], limit: usize) -> Vec<SearchCandidate> {
+    let query_variants = normalize(query);
+    if query_variants.iter().all(|tokens|tokens.is_empty()) {
+        return Vec::new();
+    }
+
+    let mut candidates: Vec<SearchCandidate> = classroom_names
+        .iter()
+        .filter_map(|name|{
+            let name_tokens = tokenize(&name.to_lowercase().replace('ё', "е"));
+            let score = query_variants
+                .iter()
+                .map(|query_tokens|score(query_tokens, &name_tokens))
+                .fold(0.0, f64::max);
+            (score >= MIN_SCORE).then(||SearchCandidate{classroom: name.to_owned(), score})
+        })
+        .collect();
+    candidates.sort_by(|a, b|b.score.total_cmp(&a.score).then_with(||a.classroom.cmp(&b.classroom)));
+    candidates.truncate(limit);
+    candidates
+}
+
+/// Приводит запрос к набору токенов в нескольких вариантах прочтения латиницы
+///
+/// # Примечание:
+/// Регистр и разделители отбрасываются, "УК3-104" превращается в ["ук", "3", "104"]
+pub fn normalize(query: &str) -> Vec<Vec<String>> {
+    let query = query.to_lowercase().replace('ё', "е");
+    let lookalike: String = query
+        .chars()
+        .map(|c|LOOKALIKES.iter().find(|(latin, _)|*latin == c).map(|(_, cyrillic)|*cyrillic).unwrap_or(c))
+        .collect();
+    let mut variants = vec![tokenize(&lookalike), tokenize(&transliterate(&query))];
+    variants.dedup();
+    variants
+}
+
+fn transliterate(query: &str) -> String {
+    let mut result = String::with_capacity(query.len());
+    let mut rest = query;
+    'outer: while let Some(c) = rest.chars().next() {
+        for (latin, cyrillic) in TRANSLITERATION {
+            if let Some(tail) = rest.strip_prefix(latin) {
+                result.push_str(cyrillic);
+                rest = tail;
+                continue 'outer;
+            }
+        }
+        result.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+    result
+}
+
+/// Делит строку на токены по разделителям и по границам букв и цифр
+fn tokenize(value: &str) -> Vec<String> {
+    let mut tokens: Vec<String> = Vec::new();
+    let mut current = String::new();
+    for c in value.chars() {
+        if !c.is_alphanumeric() {
+            if !current.is_empty() {
+                tokens.push(std::mem::take(&mut current));
+            }
+            continue;
+        }
+        let boundary = current
+            .chars()
+            .last()
+            .is_some_and(|last|last.is_ascii_digit() != c.is_ascii_digit());
+        if boundary {
+            tokens.push(std::mem::take(&mut current));
+        }
+        current.push(c);
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+    tokens
+}
+
+/// Оценивает похожесть запроса на имя аудитории
+///
+/// # Примечание:
+/// 1.0 - точное совпадение, 0.95 - те же токены в другом порядке,
+/// дальше - по расстоянию Левенштейна и совпадению начала имени
+fn score(query_tokens: &[String], name_tokens: &[String]) -> f64 {
+    if query_tokens.is_empty() || name_tokens.is_empty() {
+        return 0.0;
+    }
+    let query_joined = query_tokens.concat();
+    let name_joined = name_tokens.concat();
+    if query_joined == name_joined {
+        return 1.0;
+    }
+
+    let mut query_sorted = query_tokens.to_vec();
+    let mut name_sorted = name_tokens.to_vec();
+    query_sorted.sort();
+    name_sorted.sort();
+    if query_sorted == name_sorted {
+        return 0.95;
+    }
+
+    let similarity = |a: &str, b: &str|{
+        let max_len = a.chars().count().max(b.chars().count()) as f64;
+        1.0 - levenshtein(a, b) as f64 / max_len
+    };
+    let mut best = similarity(&query_joined, &name_joined)
+        .max(similarity(&query_sorted.concat(), &name_sorted.concat())) * 0.9;
+    if name_joined.starts_with(&query_joined) {
+        let ratio = query_joined.chars().count() as f64 / name_joined.chars().count() as f64;
+        best = best.max(0.7 + 0.2 * ratio);
+    }
+    best
+}
+
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+    for (i, a_char) in a.chars().enumerate() {
+        current[0] = i + 1;
+        for (j, b_char) in b.iter().enumerate() {
+            let cost = usize::from(a_char != *b_char);
+            current[j + 1] = (previous[j] + cost)
+                .min(previous[j + 1] + 1)
+                .min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn names() -> Vec<String> {
+        ["УК3 104", "УК3 105", "УК3 204", "ГУК 001", "ГУК 412а", "КБ 102а"]
+            .iter()
+            .map(|x|x.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_normalize(){
+        assert!(normalize("УК-3 104").contains(&vec!["ук".to_owned(), "3".to_owned(), "104".to_owned()]));
+        assert!(normalize("uk3-104").contains(&vec!["ук".to_owned(), "3".to_owned(), "104".to_owned()]));
+        assert!(normalize("YK3 104").contains(&vec!["ук".to_owned(), "3".to_owned(), "104".to_owned()]));
+    }
+
+    #[test]
+    fn test_search_exact_variants(){
+        for query in ["УК3 104", "ук3 104", "УК-3 104", "uk3-104", "UK3_104", "YK3 104"] {
+            let candidates = search(query, &names(), 5);
+            assert_eq!(candidates[0], SearchCandidate{classroom: "УК3 104".to_owned(), score: 1.0}, "{query}");
+        }
+    }
+
+    #[test]
+    fn test_search_reordered(){
+        let candidates = search("104 УК3", &names(), 5);
+        assert_eq!(candidates[0].classroom, "УК3 104");
+        assert_eq!(candidates[0].score, 0.95);
+
+        let candidates = search("guk 412a", &names(), 5);
+        assert_eq!(candidates[0].classroom, "ГУК 412а");
+    }
+
+    #[test]
+    fn test_search_ranking(){
+        let candidates = search("УК3 106", &names(), 3);
+        assert_eq!(candidates.len(), 3);
+        assert!(candidates.windows(2).all(|x|x[0].score >= x[1].score));
+        assert!(candidates.iter().all(|x|x.classroom.starts_with("УК3")));
+    }
+
+    #[test]
+    fn test_search_nothing(){
+        assert!(search("", &names(), 5).is_empty());
+        assert!(search("---", &names(), 5).is_empty());
+        assert!(search("библиотека", &names(), 5).is_empty());
+    }
+}
@@ -0,0 +1,106 @@
+use sha2::{Digest, Sha256};
+use std::str::FromStr;
+
+/// Когда заполнять Монго-базу данными из classrooms.json и images.json
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SeedPolicy{
+    /// При каждом запуске
+    Always,
+    /// Только если в базе нет ни аудиторий, ни картинок
+    IfEmpty,
+    /// Только если файлы изменились с прошлого заполнения (по контрольной сумме)
+    #[default]
+    IfChanged,
+    /// Никогда, база ведется только через админский API
+    Never,
+}
+
+impl FromStr for SeedPolicy{
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().replace('_', "-").as_str() {
+            "always" => Ok(Self::Always),
+            "if-empty" => Ok(Self::IfEmpty),
+            "if-changed" => Ok(Self::IfChanged),
+            "never" => Ok(Self::Never),
+            other => Err(format!("Unknown seed policy: {}, expected always/if-empty/if-changed/never", other)),
+        }
+    }
+}
+
+/// Версия набора данных, которым заполнена база
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq)]
+pub struct DatasetVersion{
+    /// Порядковый номер заполнения, растет при каждом изменении файлов
+    pub version: i64,
+    /// SHA-256 от содержимого classrooms.json и images.json
+    pub checksum: String,
+    /// Сколько раз данные менялись через админский API после заполнения этой версии
+    #[serde(default)]
+    pub edits: i64,
+}
+
+impl DatasetVersion{
+    /// Первая версия набора данных
+    pub fn first(checksum: String) -> Self {
+        Self{version: 1, checksum, edits: 0}
+    }
+
+    /// Версия после записи набора данных с контрольной суммой checksum поверх этой
+    ///
+    /// # Примечание:
+    /// Новые файлы получают следующий номер. Те же файлы поверх правок откатывают правки,
+    /// поэтому это считается еще одной правкой
+    pub fn next(&self, checksum: String) -> Self {
+        match (checksum == self.checksum, self.edits) {
+            (false, _) => Self{version: self.version + 1, checksum, edits: 0},
+            (true, 0) => self.clone(),
+            (true, edits) => Self{edits: edits + 1, ..self.clone()},
+        }
+    }
+}
+
+/// Считает контрольную сумму набора данных
+///
+/// # Аргументы:
+/// - classroom_data: содержимое classrooms.json
+/// - image_data: содержимое images.json
+pub fn dataset_checksum(classroom_data: &str, image_data: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(classroom_data.len().to_le_bytes());
+    hasher.update(classroom_data.as_bytes());
+    hasher.update(image_data.as_bytes());
+    hex::encode(hasher.finalize())
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn test_seed_policy_parse(){
+        assert_eq!("always".parse::<SeedPolicy>(), Ok(SeedPolicy::Always));
+        assert_eq!("IF_EMPTY".parse::<SeedPolicy>(), Ok(SeedPolicy::IfEmpty));
+        assert_eq!("if-changed".parse::<SeedPolicy>(), Ok(SeedPolicy::IfChanged));
+        assert_eq!(" never ".parse::<SeedPolicy>(), Ok(SeedPolicy::Never));
+        assert!("sometimes".parse::<SeedPolicy>().is_err());
+    }
+
+    #[test]
+    fn test_dataset_version_next(){
+        let first = DatasetVersion::first("a".to_owned());
+        assert_eq!(first.next("a".to_owned()), first);
+        assert_eq!(first.next("b".to_owned()), DatasetVersion{version: 2, checksum: "b".to_owned(), edits: 0});
+        let edited = DatasetVersion{edits: 3, ..first};
+        assert_eq!(edited.next("a".to_owned()).edits, 4);
+        assert_eq!(edited.next("b".to_owned()).edits, 0);
+    }
+
+    #[test]
+    fn test_dataset_checksum(){
+        assert_eq!(dataset_checksum("[]", "[]"), dataset_checksum("[]", "[]"));
+        assert_ne!(dataset_checksum("[]", "[]"), dataset_checksum("[] ", "[]"));
+        assert_ne!(dataset_checksum("[1]", "[]"), dataset_checksum("[", "1][]"));
+    }
+}
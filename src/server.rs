@@ -1,13 +1,112 @@
 use actix_web::dev::ServiceResponse;
-use actix_web::{get, App, HttpServer, Responder, HttpResponse, web};
-use super::mongo_client::DBClient;
+use futures::future::{Either, LocalBoxFuture};
+use actix_web::http::{header, StatusCode};
+use actix_web::{get, App, HttpRequest, HttpServer, Responder, HttpResponse, web};
+use super::admin::{self, AdminToken};
+use super::api;
+use super::blob_store::ImageStorage;
+use super::cache::{CacheConfig, CachedStore, ResponseCache};
+use super::error::{self, ApiError, StoreError};
+use super::fallback::{connect_with_retry, Backoff, FallbackStore};
+use super::floor_plan;
+use super::health;
+use super::images::ImageResource;
+use super::maps::{CampusMap, MapCatalog};
+use super::memory_store::MemoryStore;
+use super::metrics::{self, MeteredStore, Metrics};
+use super::models::ClassroomData;
+use super::mongo_client::{DBClient, MongoSettings};
+use super::negotiate::{self, JSON_CONTENT_TYPE};
+use super::reload::{DataFiles, Reloader};
+use super::route::{CampusGraph, Route};
+use super::search::{search, SearchCandidate};
+use super::seed::SeedPolicy;
+use super::store::NavigationStore;
+use super::telemetry;
+use super::thumbnails::{self, VariantCache, VariantFormat, VariantSpec};
+use serde_json::json;
 use std::error::Error;
-use std::sync::Mutex; 
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
 
+/// Общее для всех обработчиков хранилище навигационных данных
+///
+/// # Примечание:
+/// Хранилища сами отвечают за синхронизацию (клиент Монго потокобезопасен,
+/// MemoryStore держит данные под RwLock), поэтому запросы обрабатываются параллельно
+pub(crate) type SharedStore = web::Data<dyn NavigationStore>;
+
+/// Сколько подсказок "возможно, вы имели в виду" выдавать при ненайденной аудитории
+const SUGGESTION_COUNT: usize = 5;
+
+/// Сколько секунд клиенты могут кэшировать картинки
+const IMAGE_MAX_AGE: u32 = 86400;
+
+/// Как отдавать картинки в ответе /classroom
+#[derive(Debug, serde::Serialize, serde::Deserialize, utoipa::ToSchema, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum ImageMode{
+    /// Содержимое картинок в Base64
+    #[default]
+    Inline,
+    /// Ссылки вида /images/{name}
+    Url,
+}
 
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
 struct ClassroomDataRequest{
     name: String,
+    #[serde(default)]
+    images: ImageMode,
+}
+
+/// Размер и формат картинки для /images/{name}, без параметров отдается оригинал
+#[derive(Debug, serde::Serialize, serde::Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct ImageRequest{
+    /// Ширина в пикселях
+    w: Option<u32>,
+    /// Высота в пикселях
+    h: Option<u32>,
+    format: Option<VariantFormat>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct SearchRequest{
+    /// Имя аудитории целиком или частично, с опечатками
+    q: String,
+    /// Сколько вариантов выдать, по умолчанию 10
+    limit: Option<usize>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct RouteRequest{
+    /// Откуда: имя аудитории
+    from: String,
+    /// Куда: имя аудитории
+    to: String,
+}
+
+/// Карта в ответе /maps и /maps/{id}
+#[derive(Debug, serde::Serialize, serde::Deserialize, utoipa::ToSchema)]
+pub(crate) struct MapResponse{
+    #[serde(flatten)]
+    map: CampusMap,
+    image_url: String,
+    /// Содержимое картинки в Base64, только в /maps/{id}
+    #[serde(skip_serializing_if = "Option::is_none")]
+    image_data: Option<String>,
+}
+
+impl MapResponse{
+    /// # Аргументы:
+    /// - base: префикс ссылки на картинку, "" для старых путей или API_V1
+    pub(crate) fn new(map: &CampusMap, image_data: Option<String>, base: &str) -> Self {
+        Self{map: map.clone(), image_url: image_url(base, &map.image), image_data}
+    }
 }
 
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
@@ -21,30 +120,252 @@ async fn get_test_query(info: web::Query<TestQuery>) -> impl Responder {
 }
 
 #[get("/classroomlist")]
-async fn get_classroom_list(db_client: web::Data<Mutex<DBClient>>) -> impl Responder {
-    let db_client = db_client.lock().unwrap();
-    match db_client.get_classroom_list().await {
-        Ok(val) => {HttpResponse::Ok().body(val)},
-        Err(e) => {HttpResponse::NotFound().body(format!("Error: classroom list not available\nReason:{:?}", e))}
-    }
+async fn get_classroom_list(db_client: SharedStore) -> Result<HttpResponse, ApiError> {
+    Ok(HttpResponse::Ok().content_type(JSON_CONTENT_TYPE).body(db_client.get_classroom_list().await?))
 }
 
 #[get("/classroom")]
-async fn get_classroom_data(query: web::Query<ClassroomDataRequest>, db_client: web::Data<Mutex<DBClient>>) -> impl Responder {
-    let db_client = db_client.lock().unwrap();
-    let query = query.into_inner();
-    match db_client.get_classroom_data(query.name.to_owned()).await {
-        Ok(val) => {HttpResponse::Ok().body(val)},
-        Err(e) => {HttpResponse::NotFound().body(format!("Error: classroom data not available\nReason:{:?}", e))}
+async fn get_classroom_data(query: web::Query<ClassroomDataRequest>, db_client: SharedStore, metrics: web::Data<Metrics>) -> Result<HttpResponse, ApiError> {
+    let classroom = find_classroom(db_client.as_ref(), &metrics, &query.name, query.images, "").await?;
+    Ok(HttpResponse::Ok().content_type(JSON_CONTENT_TYPE).body(serde_json::to_string(&classroom).map_err(|e|ApiError::internal(e.to_string()))?))
+}
+
+/// Выдает аудиторию с картинками в Base64 или ссылками на них
+///
+/// # Аргументы:
+/// - classroom_name: имя аудитории из запроса
+/// - images: как отдавать картинки
+/// - base: префикс ссылок на картинки, "" для старых путей или API_V1
+///
+/// # Примечание:
+/// Для ненайденной аудитории ошибка содержит подсказки did_you_mean и попадает в метрики
+pub(crate) async fn find_classroom(db_client: &dyn NavigationStore, metrics: &Metrics, classroom_name: &str, images: ImageMode, base: &str) -> Result<ClassroomData, ApiError> {
+    let classroom = match images {
+        ImageMode::Inline => with_inline_images(db_client, classroom_name).await,
+        ImageMode::Url => db_client.get_classroom(classroom_name.to_owned()).await
+            .map(|mut classroom|{
+                classroom.images = classroom.images.iter().map(|name|image_url(base, name)).collect();
+                classroom
+            }),
+    };
+    match classroom {
+        Ok(val) => Ok(val),
+        Err(e @ StoreError::ClassroomNotFound(_)) => {
+            let names = db_client.get_classroom_names().await.unwrap_or_default();
+            metrics.classroom_not_found(classroom_name, &names);
+            let did_you_mean: Vec<String> = search(classroom_name, &names, SUGGESTION_COUNT)
+                .into_iter()
+                .map(|candidate|candidate.classroom)
+                .collect();
+            Err(ApiError::from(e).with_details(json!({"did_you_mean": did_you_mean})))
+        },
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Выдает аудиторию, в которой вместо названий картинок их содержимое в Base64
+///
+/// # Примечание:
+/// Картинки загружаются одним запросом get_campus_images: ненайденные пропускаются,
+/// ошибка - только если не нашлось ни одной
+pub async fn with_inline_images(db_client: &dyn NavigationStore, classroom_name: &str) -> Result<ClassroomData, StoreError> {
+    let mut classroom = db_client.get_classroom(classroom_name.to_owned()).await?;
+    classroom.images = db_client.get_campus_images(&classroom.images).await?
+        .into_iter()
+        .map(|image|image.value)
+        .collect();
+    Ok(classroom)
+}
+
+#[get("/images/{name}")]
+async fn get_image(request: HttpRequest, path: web::Path<String>, query: web::Query<ImageRequest>, db_client: SharedStore, variants: web::Data<VariantCache>) -> Result<HttpResponse, ApiError> {
+    serve_image(&request, path.into_inner(), query.into_inner(), db_client.as_ref(), &variants).await
+}
+
+/// Отдает картинку как есть или уменьшенную и перекодированную по параметрам запроса
+pub(crate) async fn serve_image(request: &HttpRequest, image_name: String, query: ImageRequest, db_client: &dyn NavigationStore, variants: &VariantCache) -> Result<HttpResponse, ApiError> {
+    let image = db_client.get_campus_image(image_name.to_owned()).await?;
+    let resource = ImageResource::decode(&image)
+        .map_err(|e|ApiError::internal(format!("image {} is corrupted", image_name))
+            .with_details(json!({"reason": e.to_string()})))?;
+
+    if query.w.is_none() && query.h.is_none() && query.format.is_none() {
+        return Ok(image_response(request, resource.content_type, resource.etag, resource.bytes.into()));
+    }
+    let spec = VariantSpec{
+        width: query.w,
+        height: query.h,
+        format: query.format.unwrap_or(VariantFormat::from_content_type(resource.content_type))};
+    spec.validate().map_err(ApiError::bad_request)?;
+    let etag = spec.etag(&resource.etag);
+    if is_not_modified(request, &etag) {
+        return Ok(image_response(request, spec.format.content_type(), etag, web::Bytes::new()));
+    }
+
+    let variant = match variants.get(&resource.etag, &spec) {
+        Some(variant) => variant,
+        None => {
+            let source = resource.bytes;
+            let variant = web::block(move ||thumbnails::render_variant(&source, &spec).map_err(|e|e.to_string()))
+                .await
+                .map_err(|e|ApiError::internal(e.to_string()))?
+                .map_err(|e|ApiError::internal(format!("image {} can not be resized", image_name))
+                    .with_details(json!({"reason": e})))?;
+            let variant = web::Bytes::from(variant);
+            variants.insert(&resource.etag, &spec, variant.clone());
+            variant
+        },
+    };
+    Ok(image_response(request, spec.format.content_type(), etag, variant))
+}
+
+/// Есть ли у клиента картинка с таким ETag (заголовок If-None-Match)
+fn is_not_modified(request: &HttpRequest, etag: &str) -> bool {
+    request.headers()
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value|value.to_str().ok())
+        .is_some_and(|value|value.split(',').any(|tag|tag.trim() == etag || tag.trim() == "*"))
+}
+
+/// Отдает картинку с ETag и Cache-Control или 304, если она уже есть у клиента
+fn image_response(request: &HttpRequest, content_type: &str, etag: String, bytes: web::Bytes) -> HttpResponse {
+    let cache_control = (header::CACHE_CONTROL, format!("public, max-age={}", IMAGE_MAX_AGE));
+    if is_not_modified(request, &etag) {
+        return HttpResponse::NotModified()
+            .insert_header((header::ETAG, etag))
+            .insert_header(cache_control)
+            .finish();
+    }
+    HttpResponse::Ok()
+        .content_type(content_type)
+        .insert_header((header::ETAG, etag))
+        .insert_header(cache_control)
+        .body(bytes)
+}
+
+#[get("/classroom/plan")]
+async fn get_classroom_plan(query: web::Query<ClassroomDataRequest>, db_client: SharedStore, maps: web::Data<MapCatalog>) -> Result<HttpResponse, ApiError> {
+    render_plan(db_client.as_ref(), &maps, &query.name).await
+}
+
+/// Отдает PNG плана этажа с выделенной аудиторией
+pub(crate) async fn render_plan(db_client: &dyn NavigationStore, maps: &MapCatalog, classroom_name: &str) -> Result<HttpResponse, ApiError> {
+    let classroom = db_client.get_classroom(classroom_name.to_owned()).await?;
+    let plan_not_found = |message: &str|ApiError::not_found("plan_not_found", format!("{}: {}", message, classroom.classroom));
+    let room = classroom.plan.as_ref().ok_or_else(||plan_not_found("Classroom is not marked on a floor plan"))?;
+    let floor_map = maps.room_plan(room, &classroom.location)
+        .ok_or_else(||plan_not_found("No floor plan in maps.json"))?;
+    let image = db_client.get_campus_image(floor_map.image.to_owned()).await?;
+    let corrupted = |e: String|ApiError::internal(format!("image {} is corrupted", image.name))
+        .with_details(json!({"reason": e}));
+    let resource = ImageResource::decode(&image).map_err(|e|corrupted(e.to_string()))?;
+    let size = floor_plan::plan_size(&resource.bytes).map_err(|e|corrupted(e.to_string()))?;
+    // Разметка за пределами плана - ошибка в данных, а не в сервере
+    floor_plan::check_points(room, size)
+        .map_err(|e|ApiError::new(StatusCode::UNPROCESSABLE_ENTITY, "invalid_data", format!("Classroom marking does not fit floor plan {}", floor_map.id))
+            .with_details(json!({"reason": e})))?;
+
+    let room = room.clone();
+    let rendered = web::block(move ||floor_plan::highlight_room(&resource.bytes, &room).map_err(|e|e.to_string()))
+        .await
+        .map_err(|e|ApiError::internal(e.to_string()))?
+        .map_err(|e|ApiError::internal(format!("floor plan {} not rendered", floor_map.id))
+            .with_details(json!({"reason": e})))?;
+    Ok(HttpResponse::Ok().content_type("image/png").body(rendered))
+}
+
+/// Ссылка на картинку для режима images=url
+///
+/// # Аргументы:
+/// - base: префикс пути, "" для /images/{name} или API_V1 для /api/v1/images/{name}
+pub(crate) fn image_url(base: &str, image_name: &str) -> String {
+    format!("{}/images/{}", base, urlencoding::encode(image_name))
+}
+
+#[get("/search")]
+async fn search_classrooms(query: web::Query<SearchRequest>, db_client: SharedStore) -> Result<HttpResponse, ApiError> {
+    Ok(HttpResponse::Ok().json(find_candidates(db_client.as_ref(), &query).await?))
+}
+
+/// Ищет аудитории по имени с опечатками
+pub(crate) async fn find_candidates(db_client: &dyn NavigationStore, query: &SearchRequest) -> Result<Vec<SearchCandidate>, ApiError> {
+    if query.q.trim().is_empty() {
+        return Err(ApiError::bad_request("empty search query"));
     }
+    let names = db_client.get_classroom_names().await?;
+    Ok(search(&query.q, &names, query.limit.unwrap_or(10)))
 }
 
 #[get("/map")]
-async fn get_general_map(db_client: web::Data<Mutex<DBClient>>) -> impl Responder {
-    let db_client = db_client.lock().unwrap();
-    match db_client.get_general_map().await {
-        Ok(val) => {HttpResponse::Ok().body(val)},
-        Err(e) => {HttpResponse::NotFound().body(format!("Error: general map not available\nReason:{:?}", e))}
+async fn get_general_map(db_client: SharedStore, maps: web::Data<MapCatalog>) -> Result<HttpResponse, ApiError> {
+    Ok(HttpResponse::Ok().content_type(JSON_CONTENT_TYPE).body(db_client.get_map(maps.general().image.to_owned()).await?))
+}
+
+#[get("/maps")]
+async fn get_map_list(maps: web::Data<MapCatalog>) -> impl Responder {
+    let maps: Vec<MapResponse> = maps.maps().iter().map(|map|MapResponse::new(map, None, "")).collect();
+    HttpResponse::Ok().json(maps)
+}
+
+#[get("/maps/{id}")]
+async fn get_map(path: web::Path<String>, db_client: SharedStore, maps: web::Data<MapCatalog>) -> Result<HttpResponse, ApiError> {
+    Ok(HttpResponse::Ok().json(find_map(db_client.as_ref(), &maps, &path, "").await?))
+}
+
+/// Выдает карту по id вместе с содержимым картинки
+///
+/// # Примечание:
+/// Для ненайденной карты ошибка содержит id всех карт
+pub(crate) async fn find_map(db_client: &dyn NavigationStore, maps: &MapCatalog, map_id: &str, base: &str) -> Result<MapResponse, ApiError> {
+    let map = maps.get(map_id).ok_or_else(||{
+        let ids: Vec<&str> = maps.maps().iter().map(|map|map.id.as_str()).collect();
+        ApiError::not_found("map_not_found", format!("Map not found: {}", map_id))
+            .with_details(json!({"maps": ids}))
+    })?;
+    let image = db_client.get_campus_image(map.image.to_owned()).await?;
+    Ok(MapResponse::new(map, Some(image.value), base))
+}
+
+#[get("/route")]
+async fn get_route(query: web::Query<RouteRequest>, db_client: SharedStore, campus_graph: web::Data<CampusGraph>) -> Result<HttpResponse, ApiError> {
+    Ok(HttpResponse::Ok().json(plan_route(db_client.as_ref(), &campus_graph, &query).await?))
+}
+
+/// Строит маршрут между двумя аудиториями
+pub(crate) async fn plan_route(db_client: &dyn NavigationStore, campus_graph: &CampusGraph, query: &RouteRequest) -> Result<Route, ApiError> {
+    let from = db_client.get_classroom(query.from.to_owned()).await?;
+    let to = db_client.get_classroom(query.to.to_owned()).await?;
+    campus_graph.plan_route(&from, &to)
+        .map_err(|e|ApiError::not_found("route_not_found", format!("no route from {} to {}", query.from, query.to))
+            .with_details(json!({"reason": e.to_string()})))
+}
+
+/// Делает необязательную часть сервера (перезагрузчик, кэш) доступной обработчикам, если она настроена
+fn register_optional<T: ?Sized + 'static>(cfg: &mut web::ServiceConfig, data: &Option<web::Data<T>>) {
+    if let Some(data) = data {
+        cfg.app_data(data.clone());
+    }
+}
+
+/// Где сервер хранит навигационные данные
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StorageKind{
+    /// Монго-база, см. DBClient
+    #[default]
+    Mongo,
+    /// Память процесса, см. MemoryStore. Не требует внешних сервисов
+    Memory,
+}
+
+impl FromStr for StorageKind{
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim() {
+            "mongo" => Ok(Self::Mongo),
+            "memory" => Ok(Self::Memory),
+            _ => Err(format!("Unknown storage: {}, expected mongo/memory", value)),
+        }
     }
 }
 
@@ -53,6 +374,19 @@ pub struct Server{
     port: u16,
     classroom_data: String,
     image_data: String,
+    campus_graph: String,
+    maps: Option<String>,
+    workers: Option<usize>,
+    storage: StorageKind,
+    mongo: MongoSettings,
+    seed_policy: SeedPolicy,
+    image_storage: ImageStorage,
+    admin_token: Option<String>,
+    data_files: Option<DataFiles>,
+    reload_interval: Duration,
+    store: Option<Arc<dyn NavigationStore>>,
+    cache: Option<CacheConfig>,
+    variant_cache_size: usize,
 }
 
 impl Server{
@@ -61,29 +395,173 @@ impl Server{
             host: None,
             port: None,
             classroom_data: None,
-            image_data: None }
+            image_data: None,
+            campus_graph: None,
+            maps: None,
+            workers: None,
+            storage: None,
+            mongo: None,
+            seed_policy: None,
+            image_storage: None,
+            admin_token: None,
+            data_files: None,
+            reload_interval: None,
+            store: None,
+            cache: None,
+            variant_cache_size: None }
+    }
+
+    /// Создает хранилище выбранного в билдере типа, с кэшем перед ним, если он включен
+    ///
+    /// # Примечание:
+    /// Задержки хранилища замеряются под кэшем, то есть только для настоящих запросов к базе.
+    /// К Монго-базе подключается с повторами, а если в настройках включен fallback, кладет перед ней
+    /// копию данных в памяти - ее фоновую проверку (FallbackStore::run) запускает start
+    async fn create_store(self, metrics: Arc<Metrics>) -> Result<(SharedStore, Option<web::Data<ResponseCache>>, Option<Arc<FallbackStore>>), Box<dyn Error>> {
+        let cache_config = self.cache;
+        let use_fallback = self.store.is_none() && self.storage == StorageKind::Mongo && self.mongo.fallback;
+        let backoff = Backoff::up_to(Duration::from_secs(self.mongo.retry_max_delay_secs));
+        let refresh_interval = Duration::from_secs(self.mongo.snapshot_refresh_secs);
+        let store: Arc<dyn NavigationStore> = match (self.store, self.storage) {
+            (Some(store), _) => store,
+            (None, StorageKind::Mongo) => {
+                let (mongo, classroom_data, image_data, image_storage) = (self.mongo, self.classroom_data, self.image_data, self.image_storage);
+                let client = connect_with_retry(mongo.startup_retries, backoff, ||{
+                    DBClient::new(mongo.clone(), classroom_data.clone(), image_data.clone(), self.seed_policy, image_storage.clone())
+                }).await?;
+                Arc::new(client)
+            },
+            (None, StorageKind::Memory) => Arc::new(MemoryStore::new(self.classroom_data, self.image_data)?),
+        };
+        let store: Arc<dyn NavigationStore> = Arc::new(MeteredStore::new(store, metrics));
+        let fallback = match use_fallback {
+            true => Some(Arc::new(FallbackStore::new(store.clone(), backoff, refresh_interval).await?)),
+            false => None,
+        };
+        let store: Arc<dyn NavigationStore> = match &fallback {
+            Some(fallback) => fallback.clone(),
+            None => store,
+        };
+        match cache_config {
+            Some(config) => {
+                let cache = Arc::new(ResponseCache::new(config));
+                let store: Arc<dyn NavigationStore> = Arc::new(CachedStore::new(store, cache.clone()));
+                Ok((web::Data::from(store), Some(web::Data::from(cache)), fallback))
+            },
+            None => Ok((web::Data::from(store), None, fallback)),
+        }
     }
+
     pub async fn start(self) -> Result<(), Box<dyn Error>> {
-        let mongo_client = web::Data::new(Mutex::new(DBClient::new(self.classroom_data, self.image_data).await?));
-        HttpServer::new(move ||{
+        let address = (self.host.clone(), self.port);
+        tracing::info!(host = %self.host, port = self.port, storage = ?self.storage, "starting navigation service");
+        let campus_graph = web::Data::new(CampusGraph::from_json(&self.campus_graph)?);
+        let maps = web::Data::new(match &self.maps {
+            Some(data) => MapCatalog::from_json(data)?,
+            None => MapCatalog::default(),
+        });
+        let admin_token = web::Data::new(AdminToken(self.admin_token.clone()));
+        let variants = web::Data::new(VariantCache::new(self.variant_cache_size));
+        let (data_files, reload_interval, workers) = (self.data_files.clone(), self.reload_interval, self.workers);
+        let metrics = Arc::new(Metrics::default());
+        let (store, cache, fallback) = self.create_store(metrics.clone()).await?;
+        let metrics = web::Data::from(metrics);
+        let reloader = data_files.map(|files|web::Data::new(Reloader::new(files, store.clone())));
+        let app_reloader = reloader.clone();
+        let server = HttpServer::new(move ||{
             App::new()
-                .app_data(mongo_client.clone())
+                .wrap_fn(negotiate::negotiate)
+                .wrap_fn(api::mark_deprecated)
+                .wrap_fn(metrics::track)
+                .wrap_fn(telemetry::trace_request)
+                .app_data(store.clone())
+                .app_data(campus_graph.clone())
+                .app_data(maps.clone())
+                .app_data(admin_token.clone())
+                .app_data(variants.clone())
+                .app_data(metrics.clone())
+                .configure(|cfg|register_optional(cfg, &app_reloader))
+                .configure(|cfg|register_optional(cfg, &cache))
+                .configure(error::configure)
+                .configure(api::configure)
                 .service(get_classroom_list)
                 .service(get_classroom_data)
-        })
-        .bind((self.host, self.port))?
-        .run()
-        .await?;
+                .service(get_classroom_plan)
+                .service(search_classrooms)
+                .service(get_route)
+                .service(get_image)
+                .service(get_general_map)
+                .service(get_map_list)
+                .service(get_map)
+                .configure(health::configure)
+                .configure(metrics::configure)
+                .configure(admin::configure)
+        });
+        let server = match workers {
+            Some(workers) => server.workers(workers),
+            None => server,
+        };
+        let server = server.bind(address)?.run();
+
+        let mut background: Vec<LocalBoxFuture<()>> = Vec::new();
+        if let Some(reloader) = &reloader {
+            background.push(Box::pin(reloader.run(reload_interval)));
+        }
+        if let Some(fallback) = &fallback {
+            background.push(Box::pin(fallback.run()));
+        }
+        match background.is_empty() {
+            true => server.await?,
+            false => {
+                if let Either::Left((result, _)) = futures::future::select(server, futures::future::join_all(background)).await {
+                    result?;
+                }
+            },
+        }
         Ok(())
     }
 
     pub async fn test_start(self) -> Result<impl actix_service::Service<actix_http::Request, Response = ServiceResponse, Error = actix_web::Error>, Box<dyn Error>> {
-        let mongo_client = web::Data::new(Mutex::new(DBClient::new(self.classroom_data, self.image_data).await?));
+        let campus_graph = web::Data::new(CampusGraph::from_json(&self.campus_graph)?);
+        let maps = web::Data::new(match &self.maps {
+            Some(data) => MapCatalog::from_json(data)?,
+            None => MapCatalog::default(),
+        });
+        let admin_token = web::Data::new(AdminToken(self.admin_token.clone()));
+        let variants = web::Data::new(VariantCache::new(self.variant_cache_size));
+        let data_files = self.data_files.clone();
+        let metrics = Arc::new(Metrics::default());
+        let (store, cache, _) = self.create_store(metrics.clone()).await?;
+        let metrics = web::Data::from(metrics);
+        let reloader = data_files.map(|files|web::Data::new(Reloader::new(files, store.clone())));
         let app = actix_web::test::init_service(App::new()
-            .app_data(mongo_client.clone())
+            .wrap_fn(negotiate::negotiate)
+            .wrap_fn(api::mark_deprecated)
+            .wrap_fn(metrics::track)
+            .wrap_fn(telemetry::trace_request)
+            .app_data(store.clone())
+            .app_data(campus_graph.clone())
+            .app_data(maps.clone())
+            .app_data(admin_token.clone())
+            .app_data(variants.clone())
+            .app_data(metrics.clone())
+            .configure(|cfg|register_optional(cfg, &reloader))
+            .configure(|cfg|register_optional(cfg, &cache))
+            .configure(error::configure)
+            .configure(api::configure)
             .service(get_test_query)
             .service(get_classroom_list)
-            .service(get_classroom_data))
+            .service(get_classroom_data)
+            .service(get_classroom_plan)
+            .service(search_classrooms)
+            .service(get_route)
+            .service(get_image)
+            .service(get_general_map)
+            .service(get_map_list)
+            .service(get_map)
+            .configure(health::configure)
+            .configure(metrics::configure)
+            .configure(admin::configure))
             .await;
         Ok(app)
     }
@@ -94,6 +572,19 @@ pub struct ServerBuilder{
     port: Option<u16>,
     classroom_data: Option<String>,
     image_data: Option<String>,
+    campus_graph: Option<String>,
+    maps: Option<String>,
+    workers: Option<usize>,
+    storage: Option<StorageKind>,
+    mongo: Option<MongoSettings>,
+    seed_policy: Option<SeedPolicy>,
+    image_storage: Option<ImageStorage>,
+    admin_token: Option<String>,
+    data_files: Option<DataFiles>,
+    reload_interval: Option<Duration>,
+    store: Option<Arc<dyn NavigationStore>>,
+    cache: Option<CacheConfig>,
+    variant_cache_size: Option<usize>,
 }
 
 impl ServerBuilder{
@@ -117,12 +608,104 @@ impl ServerBuilder{
         self
     }
 
+    pub fn campus_graph(mut self, value: String) -> Self {
+        self.campus_graph = Some(value);
+        self
+    }
+
+    /// Карты кампуса в виде [CampusMap] в JSON, по умолчанию одна карта general_map.png
+    pub fn maps(mut self, value: String) -> Self {
+        self.maps = Some(value);
+        self
+    }
+
+    /// Число потоков-обработчиков, по умолчанию по числу ядер
+    pub fn workers(mut self, value: usize) -> Self {
+        self.workers = Some(value);
+        self
+    }
+
+    pub fn storage(mut self, value: StorageKind) -> Self {
+        self.storage = Some(value);
+        self
+    }
+
+    /// Адрес Монго-базы и названия коллекций, по умолчанию localhost:27017 и navigationData
+    pub fn mongo(mut self, value: MongoSettings) -> Self {
+        self.mongo = Some(value);
+        self
+    }
+
+    /// Когда заполнять Монго-базу данными из classroom_data и image_data
+    pub fn seed_policy(mut self, value: SeedPolicy) -> Self {
+        self.seed_policy = Some(value);
+        self
+    }
+
+    /// Где Монго-хранилище держит содержимое картинок, по умолчанию в коллекции image_blobs
+    pub fn image_storage(mut self, value: ImageStorage) -> Self {
+        self.image_storage = Some(value);
+        self
+    }
+
+    /// Токен для /admin/... запросов, без него админский API выключен
+    pub fn admin_token(mut self, value: String) -> Self {
+        self.admin_token = Some(value);
+        self
+    }
+
+    /// Файлы, за которыми следить для горячей перезагрузки данных
+    pub fn data_files(mut self, value: DataFiles) -> Self {
+        self.data_files = Some(value);
+        self
+    }
+
+    /// Как часто проверять файлы с данными, по умолчанию раз в 5 секунд
+    pub fn reload_interval(mut self, value: Duration) -> Self {
+        self.reload_interval = Some(value);
+        self
+    }
+
+    /// Готовое хранилище вместо создаваемого по storage
+    ///
+    /// # Примечание:
+    /// classroom_data, image_data и seed_policy при этом не используются
+    pub fn store(mut self, value: Arc<dyn NavigationStore>) -> Self {
+        self.store = Some(value);
+        self
+    }
+
+    /// Включает кэш ответов перед хранилищем, по умолчанию кэша нет
+    pub fn cache(mut self, value: CacheConfig) -> Self {
+        self.cache = Some(value);
+        self
+    }
+
+    /// Сколько байт уменьшенных картинок держать в памяти, по умолчанию 64 МиБ, 0 отключает кэш
+    pub fn variant_cache_size(mut self, value: usize) -> Self {
+        self.variant_cache_size = Some(value);
+        self
+    }
+
     pub fn build(self) -> Server {
         Server {
             host: self.host.unwrap_or("localhost".to_string()),
             port: self.port.unwrap_or(8080),
             classroom_data: self.classroom_data.unwrap_or("[]".to_string()),
-            image_data: self.image_data.unwrap_or("[]".to_string())}
+            image_data: self.image_data.unwrap_or("[]".to_string()),
+            campus_graph: self.campus_graph.unwrap_or(r#"{"nodes": [], "edges": []}"#.to_string()),
+            maps: self.maps,
+            workers: self.workers,
+            storage: self.storage.unwrap_or_default(),
+            mongo: self.mongo.unwrap_or_default(),
+            seed_policy: self.seed_policy.unwrap_or_default(),
+            image_storage: self.image_storage.unwrap_or_default(),
+            admin_token: self.admin_token,
+            data_files: self.data_files,
+            reload_interval: self.reload_interval.unwrap_or(Duration::from_secs(5)),
+            store: self.store,
+            cache: self.cache,
+            variant_cache_size: self.variant_cache_size.unwrap_or(64 * 1024 * 1024)}
     }
 }
 /*
@@ -146,6 +729,8 @@ mod tests{
     use actix_service::Service;
     use actix_web::http::StatusCode;
     use super::*;
+    use super::super::models::{CampusImage, ClassroomData};
+    use super::super::store::{Dataset, StoreStatus};
 
     fn set_env_vars()
     {
@@ -168,12 +753,12 @@ mod tests{
 
     fn valid_image_data() -> String {
         json!([{
-                "image_name": "UK3-left.png",
-                "image": "bibabob",
+                "name": "UK3-left.png",
+                "value": "bibabob0",
             },
             {
-                "image_name": "UK3-right.png",
-                "image": "pipupap",
+                "name": "UK3-right.png",
+                "value": "pipupap0",
             },
             ]).to_string()
     }
@@ -186,6 +771,7 @@ mod tests{
             .port(8080)
             .classroom_data(valid_classroom_data())
             .image_data(valid_image_data())
+            .storage(StorageKind::Memory)
             .build()
             .test_start()
             .await
@@ -207,11 +793,12 @@ mod tests{
     #[serial]
     async fn test_server_init(){
         set_env_vars();
-        let app = Server::builder()
+        let _app = Server::builder()
             .host("localhost".to_owned())
             .port(8080)
             .classroom_data(valid_classroom_data())
             .image_data(valid_image_data())
+            .storage(StorageKind::Memory)
             .build()
             .test_start()
             .await
@@ -227,6 +814,7 @@ mod tests{
             .port(8080)
             .classroom_data(valid_classroom_data())
             .image_data(valid_image_data())
+            .storage(StorageKind::Memory)
             .build()
             .test_start()
             .await
@@ -240,7 +828,6 @@ mod tests{
         assert_eq!(res_status, StatusCode::OK);
         assert_eq!(res_body, awaited_body);
     }
-/*
     #[actix_web::test]
     #[serial]
     async fn test_classroom_data_bad(){
@@ -250,17 +837,52 @@ mod tests{
             .port(8080)
             .classroom_data(valid_classroom_data())
             .image_data(valid_image_data())
+            .storage(StorageKind::Memory)
             .build()
             .test_start()
             .await
             .unwrap();
-        let encoded_uri: String = url::form_urlencoded::byte_serialize("/classroom?name=УК3 104".as_bytes()).collect();
-        //panic!("URL: {encoded_uri}");
-        let bad_req = actix_web::test::TestRequest::with_uri(&encoded_uri).to_request();
+        let uri = format!("/classroom?name={}", urlencoding::encode("УК4 104"));
+        let bad_req = actix_web::test::TestRequest::with_uri(&uri).to_request();
         let bad_res = app.call(bad_req).await.unwrap();
         let bad_res_status = bad_res.status();
+        let bad_res_body: serde_json::Value = actix_web::test::read_body_json(bad_res).await;
+
+        assert_eq!(bad_res_status, StatusCode::NOT_FOUND);
+        assert_eq!(bad_res_body["code"], json!("classroom_not_found"));
+        assert_eq!(bad_res_body["details"]["did_you_mean"], json!(["УК3 104"]));
+
+        let bad_req = actix_web::test::TestRequest::with_uri("/classroom?name=104&images=base32").to_request();
+        let bad_res = app.call(bad_req).await.unwrap();
+        assert_eq!(bad_res.status(), StatusCode::BAD_REQUEST);
+        let bad_res_body: serde_json::Value = actix_web::test::read_body_json(bad_res).await;
+        assert_eq!(bad_res_body["code"], json!("bad_request"));
+    }
+
+    #[actix_web::test]
+    #[serial]
+    async fn test_search(){
+        set_env_vars();
+        let app = Server::builder()
+            .host("localhost".to_owned())
+            .port(8080)
+            .classroom_data(valid_classroom_data())
+            .image_data(valid_image_data())
+            .storage(StorageKind::Memory)
+            .build()
+            .test_start()
+            .await
+            .unwrap();
+        let req = actix_web::test::TestRequest::with_uri("/search?q=uk3-205").to_request();
+        let res = app.call(req).await.unwrap();
+        let res_status = res.status();
+        let res_body: serde_json::Value = actix_web::test::read_body_json(res).await;
+        assert_eq!(res_status, StatusCode::OK);
+        assert_eq!(res_body[0], json!({"classroom": "УК3 205", "score": 1.0}));
 
-        assert_ne!(bad_res_status, StatusCode::OK);
+        let req = actix_web::test::TestRequest::with_uri("/search?q=").to_request();
+        let res = app.call(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
     }
 
     #[actix_web::test]
@@ -272,28 +894,406 @@ mod tests{
             .port(8080)
             .classroom_data(valid_classroom_data())
             .image_data(valid_image_data())
+            .storage(StorageKind::Memory)
             .build()
             .test_start()
             .await
             .unwrap();
-        let encoded_uri: String = url::form_urlencoded::byte_serialize("localhost:8080/classroom?name=УК3 104".as_bytes()).collect();
-        //let encoded_uri = urlencoding::encode("/classroom?name=УК3 104").to_owned();
-        //panic!("URL: {encoded_uri}");
+        let uri = format!("/classroom?name={}", urlencoding::encode("УК3 104"));
         let req = actix_web::test::TestRequest::get()
-            .uri(&encoded_uri)
+            .uri(&uri)
             .to_request();
         let res = app.call(req).await.unwrap();
         let res_status = res.status();
-        let res_body = actix_web::test::read_body(res).await;
-        let res_body = String::from_utf8(res_body.to_vec()).unwrap();
+        let res_body: serde_json::Value = actix_web::test::read_body_json(res).await;
         let awaited_body = json!({
             "classroom": "УК3 104",
             "description": "Крутая аудитория",
-            "images": ["bibabob", "pipupap"],
-        }).to_string();
+            "images": ["bibabob0", "pipupap0"],
+            "location": {
+                "building": "УК3",
+                "floor": 1,
+                "room": "104",
+                "wing": null,
+                "suffix": null,
+                "kind": "classroom",
+            },
+        });
         assert_eq!(res_status, StatusCode::OK);
         assert_eq!(res_body, awaited_body);
     }
-*/
-}
 
+    #[actix_web::test]
+    #[serial]
+    async fn test_route(){
+        set_env_vars();
+        let campus_graph = json!({
+            "nodes": [
+                {"id": "uk3-f1", "kind": "corridor", "building": "УК3", "floor": 1, "name": "коридор 1 этажа УК3"},
+                {"id": "uk3-f1-stairs", "kind": "stairwell", "building": "УК3", "floor": 1, "name": "лестница УК3"},
+                {"id": "uk3-f2-stairs", "kind": "stairwell", "building": "УК3", "floor": 2, "name": "лестница УК3"},
+                {"id": "uk3-f2", "kind": "corridor", "building": "УК3", "floor": 2, "name": "коридор 2 этажа УК3"},
+            ],
+            "edges": [
+                {"from": "uk3-f1", "to": "uk3-f1-stairs", "distance": 20},
+                {"from": "uk3-f1-stairs", "to": "uk3-f2-stairs", "distance": 15},
+                {"from": "uk3-f2-stairs", "to": "uk3-f2", "distance": 20},
+            ],
+        }).to_string();
+        let app = Server::builder()
+            .host("localhost".to_owned())
+            .port(8080)
+            .classroom_data(valid_classroom_data())
+            .image_data(valid_image_data())
+            .campus_graph(campus_graph)
+            .storage(StorageKind::Memory)
+            .build()
+            .test_start()
+            .await
+            .unwrap();
+        let uri = format!("/route?from={}&to={}", urlencoding::encode("УК3 104"), urlencoding::encode("УК3 205"));
+        let req = actix_web::test::TestRequest::with_uri(&uri).to_request();
+        let res = app.call(req).await.unwrap();
+        let res_status = res.status();
+        let res_body: serde_json::Value = actix_web::test::read_body_json(res).await;
+        assert_eq!(res_status, StatusCode::OK);
+        assert_eq!(res_body["distance"], json!(55));
+        assert_eq!(res_body["steps"][2]["instruction"], json!("Поднимитесь по лестнице на 2 этаж"));
+        assert_eq!(res_body["steps"][4]["images"], json!(["UK3-left.png", "UK3-right.png"]));
+
+        let uri = format!("/route?from={}&to={}", urlencoding::encode("УК3 104"), urlencoding::encode("УК4 104"));
+        let req = actix_web::test::TestRequest::with_uri(&uri).to_request();
+        let res = app.call(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[actix_web::test]
+    #[serial]
+    async fn test_classroom_data_image_urls(){
+        set_env_vars();
+        let app = Server::builder()
+            .host("localhost".to_owned())
+            .port(8080)
+            .classroom_data(valid_classroom_data())
+            .image_data(valid_image_data())
+            .storage(StorageKind::Memory)
+            .build()
+            .test_start()
+            .await
+            .unwrap();
+        let uri = format!("/classroom?name={}&images=url", urlencoding::encode("УК3 104"));
+        let req = actix_web::test::TestRequest::with_uri(&uri).to_request();
+        let res = app.call(req).await.unwrap();
+        let res_status = res.status();
+        let res_body: serde_json::Value = actix_web::test::read_body_json(res).await;
+        assert_eq!(res_status, StatusCode::OK);
+        assert_eq!(res_body["images"], json!(["/images/UK3-left.png", "/images/UK3-right.png"]));
+    }
+
+    #[actix_web::test]
+    #[serial]
+    async fn test_image(){
+        set_env_vars();
+        let image_data = json!([{"name": "UK3-left.png", "value": "iVBORw0KGgo="}]).to_string();
+        let app = Server::builder()
+            .host("localhost".to_owned())
+            .port(8080)
+            .classroom_data(valid_classroom_data())
+            .image_data(image_data)
+            .storage(StorageKind::Memory)
+            .build()
+            .test_start()
+            .await
+            .unwrap();
+        let req = actix_web::test::TestRequest::with_uri("/images/UK3-left.png").to_request();
+        let res = app.call(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers().get(header::CONTENT_TYPE).unwrap(), "image/png");
+        assert!(res.headers().contains_key(header::CACHE_CONTROL));
+        let etag = res.headers().get(header::ETAG).unwrap().clone();
+        let res_body = actix_web::test::read_body(res).await;
+        assert_eq!(res_body.as_ref(), b"\x89PNG\r\n\x1a\n");
+
+        let req = actix_web::test::TestRequest::with_uri("/images/UK3-left.png")
+            .insert_header((header::IF_NONE_MATCH, etag))
+            .to_request();
+        let res = app.call(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+
+        let req = actix_web::test::TestRequest::with_uri("/images/UK3-back.png").to_request();
+        let res = app.call(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[actix_web::test]
+    #[serial]
+    async fn test_image_variants(){
+        use base64::Engine;
+        let mut picture = Vec::new();
+        image::RgbaImage::from_pixel(400, 200, image::Rgba([0, 128, 0, 255]))
+            .write_to(&mut std::io::Cursor::new(&mut picture), image::ImageOutputFormat::Png)
+            .unwrap();
+        let image_data = json!([
+            {"name": "UK3-left.png", "value": base64::engine::general_purpose::STANDARD.encode(&picture)},
+            {"name": "UK3-right.png", "value": "pipupap0"},
+        ]).to_string();
+        let app = Server::builder()
+            .classroom_data(valid_classroom_data())
+            .image_data(image_data)
+            .storage(StorageKind::Memory)
+            .build()
+            .test_start()
+            .await
+            .unwrap();
+
+        let req = actix_web::test::TestRequest::with_uri("/images/UK3-left.png?w=100&format=jpeg").to_request();
+        let res = app.call(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers().get(header::CONTENT_TYPE).unwrap(), "image/jpeg");
+        let etag = res.headers().get(header::ETAG).unwrap().clone();
+        let res_body = actix_web::test::read_body(res).await;
+        let thumbnail = image::load_from_memory(&res_body).unwrap();
+        assert_eq!((thumbnail.width(), thumbnail.height()), (100, 50));
+
+        let req = actix_web::test::TestRequest::with_uri("/images/UK3-left.png?w=100&format=jpeg")
+            .insert_header((header::IF_NONE_MATCH, etag.clone()))
+            .to_request();
+        assert_eq!(app.call(req).await.unwrap().status(), StatusCode::NOT_MODIFIED);
+
+        let req = actix_web::test::TestRequest::with_uri("/images/UK3-left.png?h=20").to_request();
+        let res = app.call(req).await.unwrap();
+        assert_eq!(res.headers().get(header::CONTENT_TYPE).unwrap(), "image/png");
+        assert_ne!(res.headers().get(header::ETAG).unwrap(), etag);
+
+        for uri in ["/images/UK3-left.png?w=0", "/images/UK3-left.png?w=5000", "/images/UK3-left.png?format=gif"] {
+            let req = actix_web::test::TestRequest::with_uri(uri).to_request();
+            assert_eq!(app.call(req).await.unwrap().status(), StatusCode::BAD_REQUEST, "{}", uri);
+        }
+        let req = actix_web::test::TestRequest::with_uri("/images/UK3-right.png?w=10").to_request();
+        assert_eq!(app.call(req).await.unwrap().status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    /// Хранилище, которое отвечает на get_classroom с задержкой, как медленная база
+    struct SlowStore{
+        inner: MemoryStore,
+        delay: Duration,
+        /// Сколько раз get_classroom ждал задержку
+        calls: std::sync::atomic::AtomicU32,
+    }
+
+    #[async_trait::async_trait]
+    impl NavigationStore for SlowStore{
+        async fn get_classroom_list(&self) -> Result<String, StoreError> {
+            self.inner.get_classroom_list().await
+        }
+
+        async fn get_classroom_names(&self) -> Result<Vec<String>, StoreError> {
+            self.inner.get_classroom_names().await
+        }
+
+        async fn get_classroom(&self, classroom_name: String) -> Result<ClassroomData, StoreError> {
+            self.calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            tokio::time::sleep(self.delay).await;
+            self.inner.get_classroom(classroom_name).await
+        }
+
+        async fn get_classroom_data(&self, classroom_name: String) -> Result<String, StoreError> {
+            self.inner.get_classroom_data(classroom_name).await
+        }
+
+        async fn get_campus_image(&self, image_name: String) -> Result<CampusImage, StoreError> {
+            self.inner.get_campus_image(image_name).await
+        }
+
+        async fn get_campus_images(&self, image_names: &[String]) -> Result<Vec<CampusImage>, StoreError> {
+            self.inner.get_campus_images(image_names).await
+        }
+
+        async fn has_image(&self, image_name: String) -> Result<bool, StoreError> {
+            self.inner.has_image(image_name).await
+        }
+
+        async fn get_map(&self, image_name: String) -> Result<String, StoreError> {
+            self.inner.get_map(image_name).await
+        }
+
+        async fn get_classrooms_with_image(&self, image_name: String) -> Result<Vec<String>, StoreError> {
+            self.inner.get_classrooms_with_image(image_name).await
+        }
+
+        async fn insert_classroom(&self, classroom: ClassroomData) -> Result<(), StoreError> {
+            self.inner.insert_classroom(classroom).await
+        }
+
+        async fn replace_classroom(&self, classroom_name: String, classroom: ClassroomData) -> Result<(), StoreError> {
+            self.inner.replace_classroom(classroom_name, classroom).await
+        }
+
+        async fn delete_classroom(&self, classroom_name: String) -> Result<(), StoreError> {
+            self.inner.delete_classroom(classroom_name).await
+        }
+
+        async fn insert_image(&self, image: CampusImage) -> Result<(), StoreError> {
+            self.inner.insert_image(image).await
+        }
+
+        async fn replace_image(&self, image: CampusImage) -> Result<(), StoreError> {
+            self.inner.replace_image(image).await
+        }
+
+        async fn delete_image(&self, image_name: String) -> Result<(), StoreError> {
+            self.inner.delete_image(image_name).await
+        }
+
+        async fn reload(&self, classroom_data: String, image_data: String) -> Result<(), StoreError> {
+            self.inner.reload(classroom_data, image_data).await
+        }
+
+        async fn export(&self) -> Result<Dataset, StoreError> {
+            self.inner.export().await
+        }
+
+        async fn status(&self) -> Result<StoreStatus, StoreError> {
+            self.inner.status().await
+        }
+    }
+
+    #[actix_web::test]
+    #[serial]
+    async fn test_concurrent_requests(){
+        let delay = Duration::from_millis(200);
+        let request_count = 20;
+        let store = Arc::new(SlowStore{
+            inner: MemoryStore::new(valid_classroom_data(), valid_image_data()).unwrap(),
+            delay,
+            calls: Default::default()});
+        let app = Server::builder()
+            .store(store.clone())
+            .build()
+            .test_start()
+            .await
+            .unwrap();
+
+        let uri = format!("/classroom?name={}", urlencoding::encode("УК3 104"));
+        let started = std::time::Instant::now();
+        let responses = futures::future::join_all((0..request_count).map(|_|{
+            app.call(actix_web::test::TestRequest::with_uri(&uri).to_request())
+        })).await;
+        let elapsed = started.elapsed();
+
+        for res in responses {
+            assert_eq!(res.unwrap().status(), StatusCode::OK);
+        }
+        // Каждый запрос ждал задержку, а последовательная обработка заняла бы request_count * delay
+        assert_eq!(store.calls.load(std::sync::atomic::Ordering::SeqCst), request_count);
+        assert!(elapsed >= delay, "the store delay was not applied: {:?}", elapsed);
+        assert!(elapsed < delay * 4, "requests were not served in parallel: {:?}", elapsed);
+    }
+
+    #[actix_web::test]
+    #[serial]
+    async fn test_maps(){
+        let image_data = json!([
+            {"name": "general_map.png", "value": "bWFw"},
+            {"name": "uk3-2.png", "value": "dWszLTI="},
+        ]).to_string();
+        let maps = json!([
+            {"id": "campus", "title": "Кампус", "kind": "campus", "image": "general_map.png"},
+            {"id": "uk3-2", "title": "УК3, 2 этаж", "kind": "floor", "building": "УК3", "floor": 2, "image": "uk3-2.png"},
+        ]).to_string();
+        let app = Server::builder()
+            .classroom_data(valid_classroom_data())
+            .image_data(image_data)
+            .maps(maps)
+            .storage(StorageKind::Memory)
+            .build()
+            .test_start()
+            .await
+            .unwrap();
+
+        let req = actix_web::test::TestRequest::with_uri("/map").to_request();
+        let res = app.call(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        let res_body: serde_json::Value = actix_web::test::read_body_json(res).await;
+        assert_eq!(res_body, json!(["bWFw"]));
+
+        let req = actix_web::test::TestRequest::with_uri("/maps").to_request();
+        let res_body: serde_json::Value = actix_web::test::read_body_json(app.call(req).await.unwrap()).await;
+        assert_eq!(res_body[1], json!({
+            "id": "uk3-2",
+            "title": "УК3, 2 этаж",
+            "kind": "floor",
+            "building": "УК3",
+            "floor": 2,
+            "image": "uk3-2.png",
+            "image_url": "/images/uk3-2.png",
+        }));
+
+        let req = actix_web::test::TestRequest::with_uri("/maps/uk3-2").to_request();
+        let res_body: serde_json::Value = actix_web::test::read_body_json(app.call(req).await.unwrap()).await;
+        assert_eq!(res_body["image_data"], json!("dWszLTI="));
+
+        let req = actix_web::test::TestRequest::with_uri("/maps/uk4").to_request();
+        let res = app.call(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        let res_body: serde_json::Value = actix_web::test::read_body_json(res).await;
+        assert_eq!(res_body["details"]["maps"], json!(["campus", "uk3-2"]));
+    }
+
+    #[actix_web::test]
+    #[serial]
+    async fn test_classroom_plan(){
+        use base64::Engine;
+        let mut plan = Vec::new();
+        image::RgbaImage::from_pixel(200, 100, image::Rgba([255, 255, 255, 255]))
+            .write_to(&mut std::io::Cursor::new(&mut plan), image::ImageOutputFormat::Png)
+            .unwrap();
+        let classroom_data = json!([
+            {"classroom": "УК3 204", "description": "", "images": [], "plan": {"points": [[50, 50]]}},
+            {"classroom": "УК3 205", "description": "", "images": [], "plan": {"map": "uk3-2", "points": [[300, 50]]}},
+            {"classroom": "УК3 104", "description": "", "images": [], "plan": {"points": [[50, 50]]}},
+            {"classroom": "УК3 206", "description": "", "images": []},
+        ]).to_string();
+        let image_data = json!([
+            {"name": "general_map.png", "value": "bWFw"},
+            {"name": "uk3-2.png", "value": base64::engine::general_purpose::STANDARD.encode(&plan)},
+        ]).to_string();
+        let maps = json!([
+            {"id": "campus", "title": "Кампус", "kind": "campus", "image": "general_map.png"},
+            {"id": "uk3-2", "title": "УК3, 2 этаж", "kind": "floor", "building": "УК3", "floor": 2, "image": "uk3-2.png"},
+        ]).to_string();
+        let app = Server::builder()
+            .classroom_data(classroom_data)
+            .image_data(image_data)
+            .maps(maps)
+            .storage(StorageKind::Memory)
+            .build()
+            .test_start()
+            .await
+            .unwrap();
+
+        let plan_request = |name: &str|{
+            let uri = format!("/classroom/plan?name={}", urlencoding::encode(name));
+            actix_web::test::TestRequest::with_uri(&uri).to_request()
+        };
+        let res = app.call(plan_request("УК3 204")).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers().get(header::CONTENT_TYPE).unwrap(), "image/png");
+        let res_body = actix_web::test::read_body(res).await;
+        let rendered = image::load_from_memory(&res_body).unwrap().to_rgba8();
+        assert_eq!(rendered.dimensions(), (200, 100));
+        assert_ne!(*rendered.get_pixel(50, 50), image::Rgba([255, 255, 255, 255]));
+
+        let res = app.call(plan_request("УК3 205")).await.unwrap();
+        assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let res_body: serde_json::Value = actix_web::test::read_body_json(res).await;
+        assert_eq!(res_body["code"], "invalid_data");
+
+        for name in ["УК3 104", "УК3 206"] {
+            let res = app.call(plan_request(name)).await.unwrap();
+            assert_eq!(res.status(), StatusCode::NOT_FOUND);
+            let res_body: serde_json::Value = actix_web::test::read_body_json(res).await;
+            assert_eq!(res_body["code"], "plan_not_found");
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use async_trait::async_trait;
+use super::error::StoreError;
+use super::models::{CampusImage, ClassroomData};
+use super::seed::DatasetVersion;
+
+/// Состояние хранилища для /readyz и /version
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq)]
+pub struct StoreStatus{
+    pub classrooms: u64,
+    /// Сколько названий картинок, одинаковое содержимое считается по каждому названию
+    pub images: u64,
+    /// Каким набором данных заполнено хранилище, если это известно
+    pub dataset: Option<DatasetVersion>,
+    /// Заполнено, если база недоступна и данные отдаются из копии в памяти (см. FallbackStore)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub degraded: Option<Degradation>,
+}
+
+/// Почему и с какого момента хранилище отдает копию данных
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq)]
+pub struct Degradation{
+    /// Unix-время в секундах, когда база перестала отвечать
+    pub since: u64,
+    /// Последняя ошибка базы
+    pub error: String,
+}
+
+/// Все данные хранилища разом: из них собирается копия в памяти
+#[derive(Debug, Clone, Default)]
+pub struct Dataset{
+    pub classrooms: Vec<ClassroomData>,
+    pub images: Vec<CampusImage>,
+    pub version: Option<DatasetVersion>,
+}
+
+/// Хранилище навигационных данных
+///
+/// # Что делает?
+/// Скрывает от сервера, где именно лежат аудитории и картинки:
+/// в Монго-базе (DBClient) или в памяти процесса (MemoryStore)
+#[async_trait]
+pub trait NavigationStore: Send + Sync {
+    /// Выдает список всех аудиторий в виде JSON-строки
+    async fn get_classroom_list(&self) -> Result<String, StoreError>;
+
+    /// Выдает имена всех аудиторий
+    async fn get_classroom_names(&self) -> Result<Vec<String>, StoreError>;
+
+    /// Выдает навигационные данные аудитории с названиями картинок
+    ///
+    /// # Аргументы:
+    /// - classroom_name: Имя аудитории
+    async fn get_classroom(&self, classroom_name: String) -> Result<ClassroomData, StoreError>;
+
+    /// Выдает данные о местоположении аудитории в виде JSON-строки
+    /// с закодированными в Base64 картинками
+    ///
+    /// # Аргументы:
+    /// - classroom_name: Имя аудитории
+    async fn get_classroom_data(&self, classroom_name: String) -> Result<String, StoreError>;
+
+    /// Выдает одну картинку корпуса по ее названию
+    ///
+    /// # Аргументы:
+    /// - image_name: Название картинки, например "GUK-front.png"
+    async fn get_campus_image(&self, image_name: String) -> Result<CampusImage, StoreError>;
+
+    /// Выдает картинки корпуса по названиям одним запросом
+    ///
+    /// # Аргументы:
+    /// - image_names: Названия картинок
+    ///
+    /// # Примечание:
+    /// Картинки выдаются в порядке image_names, ненайденные пропускаются.
+    /// Выдает ошибку StoreError::ImageNotFound, только если не нашлось ни одной
+    async fn get_campus_images(&self, image_names: &[String]) -> Result<Vec<CampusImage>, StoreError>;
+
+    /// Проверяет, есть ли картинка, не загружая ее содержимое
+    ///
+    /// # Аргументы:
+    /// - image_name: Название картинки
+    async fn has_image(&self, image_name: String) -> Result<bool, StoreError>;
+
+    /// Выдает карту в виде JSON-строки [Base64], как ее отдает /map
+    ///
+    /// # Аргументы:
+    /// - image_name: Название картинки с картой из maps.json
+    async fn get_map(&self, image_name: String) -> Result<String, StoreError>;
+
+    /// Выдает имена аудиторий, которые ссылаются на картинку
+    ///
+    /// # Аргументы:
+    /// - image_name: Название картинки
+    async fn get_classrooms_with_image(&self, image_name: String) -> Result<Vec<String>, StoreError>;
+
+    /// Добавляет новую аудиторию
+    ///
+    /// # Примечание:
+    /// Выдает ошибку StoreError::Conflict, если аудитория с таким именем уже есть
+    async fn insert_classroom(&self, classroom: ClassroomData) -> Result<(), StoreError>;
+
+    /// Заменяет данные аудитории
+    ///
+    /// # Аргументы:
+    /// - classroom_name: Текущее имя аудитории
+    /// - classroom: Новые данные, имя в них может отличаться (переименование)
+    async fn replace_classroom(&self, classroom_name: String, classroom: ClassroomData) -> Result<(), StoreError>;
+
+    /// Удаляет аудиторию
+    async fn delete_classroom(&self, classroom_name: String) -> Result<(), StoreError>;
+
+    /// Добавляет новую картинку
+    ///
+    /// # Примечание:
+    /// Выдает ошибку StoreError::Conflict, если картинка с таким названием уже есть
+    async fn insert_image(&self, image: CampusImage) -> Result<(), StoreError>;
+
+    /// Заменяет содержимое картинки
+    async fn replace_image(&self, image: CampusImage) -> Result<(), StoreError>;
+
+    /// Удаляет картинку
+    async fn delete_image(&self, image_name: String) -> Result<(), StoreError>;
+
+    /// Подменяет набор данных новым из JSON-строк
+    ///
+    /// # Примечание:
+    /// Данные сначала полностью разбираются, и только потом подменяются.
+    /// Если разобрать не удалось, хранилище продолжает отдавать старые данные
+    async fn reload(&self, classroom_data: String, image_data: String) -> Result<(), StoreError>;
+
+    /// Выдает все аудитории и картинки вместе с версией набора данных
+    ///
+    /// # Примечание:
+    /// Загружает содержимое всех картинок, поэтому не для обработчиков запросов
+    async fn export(&self) -> Result<Dataset, StoreError>;
+
+    /// Проверяет, что хранилище доступно, и выдает сколько в нем данных
+    ///
+    /// # Примечание:
+    /// Не кэшируется: по нему оркестратор решает, можно ли слать запросы
+    async fn status(&self) -> Result<StoreStatus, StoreError>;
+}
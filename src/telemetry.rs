@@ -0,0 +1,142 @@
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderName, HeaderValue};
+use std::future::Future;
+use std::str::FromStr;
+use std::time::Instant;
+use tracing::Instrument;
+use tracing_subscriber::EnvFilter;
+
+/// Заголовок с идентификатором запроса
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+/// Уровень логов по умолчанию
+pub const DEFAULT_LOG_LEVEL: &str = "info";
+/// Самый длинный идентификатор запроса, который принимается от клиента
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+/// Как выводить логи
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LogFormat{
+    /// Читаемые строки для разработки
+    #[default]
+    Pretty,
+    /// Одна JSON-строка на событие, для сборщиков логов
+    Json,
+}
+
+impl FromStr for LogFormat{
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim() {
+            "pretty" => Ok(Self::Pretty),
+            "json" => Ok(Self::Json),
+            _ => Err(format!("Unknown log format: {}, expected pretty/json", value)),
+        }
+    }
+}
+
+/// Включает логи для всего процесса
+///
+/// # Аргументы:
+/// - format: формат вывода
+/// - level: фильтр в синтаксисе RUST_LOG, например "info" или "info,actix_navigation_service=debug"
+///
+/// # Примечание:
+/// На уровне debug пишется каждый запрос к хранилищу с длительностью (см. metrics::MeteredStore)
+pub fn init_logging(format: LogFormat, level: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::try_new(level)?);
+    match format {
+        LogFormat::Pretty => builder.try_init(),
+        LogFormat::Json => builder.json().with_current_span(true).with_span_list(false).try_init(),
+    }
+}
+
+/// Берет идентификатор запроса из X-Request-Id или создает новый
+///
+/// # Примечание:
+/// Чужой идентификатор принимается, только если он короткий и из печатных ASCII-символов,
+/// чтобы клиент не мог подсунуть в логи что угодно
+fn request_id(request: &ServiceRequest) -> String {
+    request.headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|x|x.to_str().ok())
+        .filter(|x|!x.is_empty() && x.len() <= MAX_REQUEST_ID_LEN && x.bytes().all(|b|b.is_ascii_graphic()))
+        .map(str::to_owned)
+        .unwrap_or_else(||uuid::Uuid::new_v4().to_string())
+}
+
+/// Middleware для App::wrap_fn: открывает спан запроса с request_id и пишет строку лога по завершении
+///
+/// # Примечание:
+/// Идентификатор возвращается клиенту в X-Request-Id, чтобы жалобу бота можно было найти в логах
+pub fn trace_request<S, B>(request: ServiceRequest, service: &S) -> impl Future<Output = Result<ServiceResponse<B>, actix_web::Error>>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error>,
+{
+    let started = Instant::now();
+    let request_id = request_id(&request);
+    let span = tracing::info_span!("request",
+        request_id = %request_id,
+        method = %request.method(),
+        path = %request.path());
+    let response = {
+        let _entered = span.enter();
+        service.call(request)
+    };
+    async move {
+        let mut response = response.await?;
+        let status = response.status();
+        let route = response.request().match_pattern().unwrap_or_else(||"unmatched".to_owned());
+        let elapsed_ms = started.elapsed().as_secs_f64() * 1000.0;
+        if status.is_server_error() {
+            tracing::error!(status = status.as_u16(), route, elapsed_ms, "request failed");
+        } else {
+            tracing::info!(status = status.as_u16(), route, elapsed_ms, "request finished");
+        }
+        if let Ok(value) = HeaderValue::from_str(&request_id) {
+            response.headers_mut().insert(HeaderName::from_static(REQUEST_ID_HEADER), value);
+        }
+        Ok(response)
+    }
+    .instrument(span)
+}
+
+#[cfg(test)]
+mod tests{
+    use serde_json::json;
+    use serial_test::serial;
+    use actix_service::Service as _;
+    use actix_web::test::TestRequest;
+    use super::*;
+    use super::super::server::{Server, StorageKind};
+
+    #[test]
+    fn test_log_format_parse(){
+        assert_eq!("json".parse::<LogFormat>(), Ok(LogFormat::Json));
+        assert_eq!(" pretty".parse::<LogFormat>(), Ok(LogFormat::Pretty));
+        assert!("xml".parse::<LogFormat>().is_err());
+    }
+
+    #[actix_web::test]
+    #[serial]
+    async fn test_request_id(){
+        let app = Server::builder()
+            .classroom_data(json!([{"classroom": "УК3 104", "description": "", "images": []}]).to_string())
+            .storage(StorageKind::Memory)
+            .build()
+            .test_start()
+            .await
+            .unwrap();
+
+        let res = app.call(TestRequest::with_uri("/healthz").insert_header((REQUEST_ID_HEADER, "bot-42")).to_request()).await.unwrap();
+        assert_eq!(res.headers().get(REQUEST_ID_HEADER).unwrap(), "bot-42");
+
+        let res = app.call(TestRequest::with_uri("/healthz").insert_header((REQUEST_ID_HEADER, "two words")).to_request()).await.unwrap();
+        let generated = res.headers().get(REQUEST_ID_HEADER).unwrap().to_str().unwrap();
+        assert!(uuid::Uuid::parse_str(generated).is_ok());
+
+        let res = app.call(TestRequest::with_uri("/nowhere").to_request()).await.unwrap();
+        assert!(res.headers().contains_key(REQUEST_ID_HEADER));
+    }
+}
@@ -0,0 +1,265 @@
+use actix_web::web::Bytes;
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::webp::WebPEncoder;
+use image::imageops::FilterType;
+use image::{ColorType, ImageOutputFormat};
+use std::collections::HashMap;
+use std::error::Error;
+use std::io::Cursor;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+/// Наибольшие ширина и высота, которые можно запросить
+pub const MAX_DIMENSION: u32 = 4096;
+/// Качество JPEG: превью заметно меньше, а на экране телефона разницы не видно
+const JPEG_QUALITY: u8 = 80;
+
+/// Формат, в который перекодируется картинка
+#[derive(Debug, serde::Serialize, serde::Deserialize, utoipa::ToSchema, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum VariantFormat{
+    Png,
+    Jpeg,
+    /// WebP без потерь: кодировщик с потерями требует libwebp
+    Webp,
+}
+
+impl VariantFormat{
+    /// Формат по Content-Type исходной картинки, незнакомые отдаются как PNG
+    pub fn from_content_type(content_type: &str) -> Self {
+        match content_type {
+            "image/jpeg" => Self::Jpeg,
+            "image/webp" => Self::Webp,
+            _ => Self::Png,
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Png => "image/png",
+            Self::Jpeg => "image/jpeg",
+            Self::Webp => "image/webp",
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Png => "png",
+            Self::Jpeg => "jpeg",
+            Self::Webp => "webp",
+        }
+    }
+}
+
+/// Какой вариант картинки запросил клиент
+///
+/// # Примечание:
+/// Картинка вписывается в width x height с сохранением пропорций и никогда не увеличивается.
+/// Если задана только одна сторона, вторая подбирается по пропорциям
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct VariantSpec{
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub format: VariantFormat,
+}
+
+impl VariantSpec{
+    /// Проверяет, что размеры от 1 до MAX_DIMENSION
+    pub fn validate(&self) -> Result<(), String> {
+        for size in [self.width, self.height].into_iter().flatten() {
+            if size == 0 || size > MAX_DIMENSION {
+                return Err(format!("image size must be between 1 and {}, got {}", MAX_DIMENSION, size));
+            }
+        }
+        Ok(())
+    }
+
+    /// Сильный ETag варианта, производный от ETag исходной картинки
+    pub fn etag(&self, source_etag: &str) -> String {
+        let size = |value: Option<u32>|value.map(|x|x.to_string()).unwrap_or_default();
+        format!("\"{}-{}x{}.{}\"",
+            source_etag.trim_matches('"'), size(self.width), size(self.height), self.format.extension())
+    }
+}
+
+/// Уменьшает и перекодирует картинку
+///
+/// # Аргументы:
+/// - source: исходная картинка (PNG, JPEG или WebP)
+/// - spec: размеры и формат результата
+pub fn render_variant(source: &[u8], spec: &VariantSpec) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut picture = image::load_from_memory(source)?;
+    let (width, height) = (picture.width(), picture.height());
+    let (max_width, max_height) = (spec.width.unwrap_or(u32::MAX), spec.height.unwrap_or(u32::MAX));
+    if width > max_width || height > max_height {
+        picture = picture.resize(max_width, max_height, FilterType::Triangle);
+    }
+
+    let mut result = Vec::new();
+    match spec.format {
+        VariantFormat::Png => picture.write_to(&mut Cursor::new(&mut result), ImageOutputFormat::Png)?,
+        VariantFormat::Jpeg => JpegEncoder::new_with_quality(&mut result, JPEG_QUALITY)
+            .encode_image(&picture.to_rgb8())?,
+        VariantFormat::Webp => {
+            let rgba = picture.to_rgba8();
+            WebPEncoder::new_lossless(&mut result).encode(rgba.as_raw(), rgba.width(), rgba.height(), ColorType::Rgba8)?
+        },
+    }
+    Ok(result)
+}
+
+/// Счетчики кэша вариантов
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub struct VariantCacheStats{
+    pub hits: u64,
+    pub misses: u64,
+    pub entries: usize,
+    /// Сколько байт занимают закэшированные варианты
+    pub bytes: usize,
+}
+
+struct CachedVariant{
+    data: Bytes,
+    last_used: u64,
+}
+
+#[derive(Default)]
+struct VariantState{
+    entries: HashMap<(String, VariantSpec), CachedVariant>,
+    bytes: usize,
+    /// Счетчик обращений, по нему вытесняются давно не запрошенные варианты
+    clock: u64,
+}
+
+/// Кэш уменьшенных картинок
+///
+/// # Примечание:
+/// Ключ - ETag исходной картинки и VariantSpec, поэтому после замены картинки
+/// старые варианты просто перестают запрашиваться и вытесняются по размеру
+pub struct VariantCache{
+    max_bytes: usize,
+    state: Mutex<VariantState>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl VariantCache{
+    /// # Аргументы:
+    /// - max_bytes: сколько байт держать, 0 отключает кэш
+    pub fn new(max_bytes: usize) -> Self {
+        Self{
+            max_bytes,
+            state: Mutex::new(VariantState::default()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0)}
+    }
+
+    pub fn get(&self, source_etag: &str, spec: &VariantSpec) -> Option<Bytes> {
+        let mut state = self.state.lock().unwrap();
+        state.clock += 1;
+        let clock = state.clock;
+        match state.entries.get_mut(&(source_etag.to_owned(), *spec)) {
+            Some(variant) => {
+                variant.last_used = clock;
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                Some(variant.data.clone())
+            },
+            None => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                None
+            },
+        }
+    }
+
+    /// Запоминает вариант, вытесняя давно не запрошенные, если не хватает места
+    pub fn insert(&self, source_etag: &str, spec: &VariantSpec, data: Bytes) {
+        if data.len() > self.max_bytes {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        while state.bytes + data.len() > self.max_bytes {
+            let oldest = state.entries.iter()
+                .min_by_key(|(_, variant)|variant.last_used)
+                .map(|(key, _)|key.clone());
+            let Some(key) = oldest else { break };
+            if let Some(variant) = state.entries.remove(&key) {
+                state.bytes -= variant.data.len();
+            }
+        }
+        state.clock += 1;
+        let variant = CachedVariant{data, last_used: state.clock};
+        state.bytes += variant.data.len();
+        if let Some(previous) = state.entries.insert((source_etag.to_owned(), *spec), variant) {
+            state.bytes -= previous.data.len();
+        }
+    }
+
+    pub fn stats(&self) -> VariantCacheStats {
+        let state = self.state.lock().unwrap();
+        VariantCacheStats{
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            entries: state.entries.len(),
+            bytes: state.bytes}
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use image::{Rgba, RgbaImage};
+    use super::*;
+
+    /// PNG 400x200: левая половина красная, правая синяя
+    fn source() -> Vec<u8> {
+        let canvas = RgbaImage::from_fn(400, 200, |x, _|{
+            if x < 200 {Rgba([255, 0, 0, 255])} else {Rgba([0, 0, 255, 255])}
+        });
+        let mut bytes = Vec::new();
+        canvas.write_to(&mut Cursor::new(&mut bytes), ImageOutputFormat::Png).unwrap();
+        bytes
+    }
+
+    fn spec(width: Option<u32>, height: Option<u32>, format: VariantFormat) -> VariantSpec {
+        VariantSpec{width, height, format}
+    }
+
+    #[test]
+    fn test_resize(){
+        let cases = [
+            (spec(Some(100), None, VariantFormat::Png), (100, 50), image::ImageFormat::Png),
+            (spec(None, Some(50), VariantFormat::Jpeg), (100, 50), image::ImageFormat::Jpeg),
+            (spec(Some(100), Some(100), VariantFormat::Webp), (100, 50), image::ImageFormat::WebP),
+            (spec(Some(1000), None, VariantFormat::Png), (400, 200), image::ImageFormat::Png),
+        ];
+        for (spec, size, format) in cases {
+            let result = render_variant(&source(), &spec).unwrap();
+            assert_eq!(image::guess_format(&result).unwrap(), format);
+            let picture = image::load_from_memory(&result).unwrap();
+            assert_eq!((picture.width(), picture.height()), size, "{:?}", spec);
+        }
+        assert!(render_variant(b"bibabob", &spec(Some(10), None, VariantFormat::Png)).is_err());
+    }
+
+    #[test]
+    fn test_spec(){
+        assert!(spec(Some(0), None, VariantFormat::Png).validate().is_err());
+        assert!(spec(None, Some(MAX_DIMENSION + 1), VariantFormat::Png).validate().is_err());
+        assert!(spec(Some(320), None, VariantFormat::Png).validate().is_ok());
+        assert_eq!(spec(Some(320), None, VariantFormat::Webp).etag("\"abc\""), "\"abc-320x.webp\"");
+    }
+
+    #[test]
+    fn test_variant_cache(){
+        let cache = VariantCache::new(10);
+        let small = spec(Some(10), None, VariantFormat::Png);
+        let large = spec(Some(20), None, VariantFormat::Png);
+        cache.insert("a", &small, Bytes::from_static(b"12345"));
+        cache.insert("a", &large, Bytes::from_static(b"12345"));
+        assert_eq!(cache.get("a", &small).unwrap(), Bytes::from_static(b"12345"));
+        cache.insert("b", &small, Bytes::from_static(b"1234"));
+        assert!(cache.get("a", &large).is_none());
+        assert!(cache.get("a", &small).is_some());
+        cache.insert("c", &small, Bytes::from_static(b"12345678901"));
+        assert_eq!(cache.stats(), VariantCacheStats{hits: 2, misses: 1, entries: 2, bytes: 9});
+    }
+}
@@ -0,0 +1,236 @@
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+use super::floor_plan;
+use super::images::ImageResource;
+use super::location::{ClassroomKind, ClassroomLocation};
+use super::maps::MapCatalog;
+use super::models::{CampusImage, ClassroomData};
+
+/// Описания-заглушки, которые считаются незаполненными
+pub const PLACEHOLDER_DESCRIPTIONS: [&str; 4] = ["описание еще не добавлено", "нет описания", "todo", "tbd"];
+
+/// Насколько серьезна найденная проблема
+#[derive(Debug, serde::Serialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity{
+    /// Данные загрузятся, но бот будет отвечать хуже
+    Warning,
+    /// Данные битые: запросы к ним будут падать
+    Error,
+}
+
+/// Проблема в наборе данных
+#[derive(Debug, serde::Serialize, Clone, PartialEq)]
+pub struct Issue{
+    pub severity: Severity,
+    /// Аудитория или картинка, к которой относится проблема
+    pub subject: String,
+    pub message: String,
+}
+
+/// Отчет о проверке classrooms.json и images.json
+#[derive(Debug, Clone, Default)]
+pub struct Report{
+    pub issues: Vec<Issue>,
+}
+
+impl Report{
+    /// Количество проблем заданной серьезности
+    pub fn count(&self, severity: Severity) -> usize {
+        self.issues.iter().filter(|x|x.severity == severity).count()
+    }
+
+    /// Есть ли в отчете ошибки
+    pub fn has_errors(&self) -> bool {
+        self.count(Severity::Error) > 0
+    }
+
+    fn push(&mut self, severity: Severity, subject: &str, message: String) {
+        self.issues.push(Issue{severity, subject: subject.to_owned(), message});
+    }
+}
+
+impl fmt::Display for Report{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for issue in &self.issues {
+            let severity = match issue.severity {
+                Severity::Error => "error",
+                Severity::Warning => "warning",
+            };
+            writeln!(f, "{}: {}: {}", severity, issue.subject, issue.message)?;
+        }
+        write!(f, "{} error(s), {} warning(s)", self.count(Severity::Error), self.count(Severity::Warning))
+    }
+}
+
+/// Проверяет набор данных
+///
+/// # Аргументы:
+/// - classroom_data: содержимое classrooms.json
+/// - image_data: содержимое images.json
+/// - map_data: содержимое maps.json, если есть: по нему разметка аудиторий сверяется с планами этажей
+///
+/// # Что делает?
+/// - Ошибки: повторяющиеся имена, ссылки на несуществующие картинки, битый Base64,
+///   разметка аудитории за пределами плана этажа
+/// - Предупреждения: неиспользуемые картинки, пустые описания и заглушки,
+///   аудитории без картинок, имена аудиторий, которые не удалось разобрать
+///
+/// # Примечание:
+/// Выдает ошибку, только если файлы не подходят под схему ClassroomData/CampusImage/CampusMap
+pub fn validate_dataset(classroom_data: &str, image_data: &str, map_data: Option<&str>) -> Result<Report, Box<dyn Error>> {
+    let classrooms: Vec<ClassroomData> = serde_json::from_str(classroom_data)
+        .map_err(|e|format!("classrooms.json does not match the schema: {}", e))?;
+    let images: Vec<CampusImage> = serde_json::from_str(image_data)
+        .map_err(|e|format!("images.json does not match the schema: {}", e))?;
+    let maps = match map_data {
+        Some(data) => Some(MapCatalog::from_json(data).map_err(|e|format!("maps.json is invalid: {}", e))?),
+        None => None,
+    };
+    let mut report = Report::default();
+
+    let mut image_names = HashSet::new();
+    for image in &images {
+        if !image_names.insert(image.name.as_str()) {
+            report.push(Severity::Error, &image.name, "duplicate image name".to_owned());
+        }
+        if let Err(e) = ImageResource::decode(image) {
+            report.push(Severity::Error, &image.name, format!("invalid Base64: {}", e));
+        }
+    }
+
+    let mut classroom_counts: HashMap<&str, usize> = HashMap::new();
+    let mut used_images = HashSet::new();
+    for classroom in &classrooms {
+        let name = classroom.classroom.as_str();
+        *classroom_counts.entry(name).or_default() += 1;
+        if classroom_counts[name] == 2 {
+            report.push(Severity::Error, name, "duplicate classroom name".to_owned());
+        }
+        for image in &classroom.images {
+            used_images.insert(image.as_str());
+            if !image_names.contains(image.as_str()) {
+                report.push(Severity::Error, name, format!("references missing image {}", image));
+            }
+        }
+        if classroom.images.is_empty() {
+            report.push(Severity::Warning, name, "no images".to_owned());
+        }
+        if classroom.description.trim().is_empty() {
+            report.push(Severity::Warning, name, "empty description".to_owned());
+        } else if is_placeholder(&classroom.description) {
+            report.push(Severity::Warning, name, format!("placeholder description \"{}\"", classroom.description));
+        }
+        if ClassroomLocation::parse(name).kind == ClassroomKind::Unknown {
+            report.push(Severity::Warning, name, "room name could not be parsed".to_owned());
+        }
+        if classroom.plan.as_ref().is_some_and(|plan|plan.points.is_empty()) {
+            report.push(Severity::Error, name, "floor plan has no points".to_owned());
+        } else if let Some(reason) = maps.as_ref().and_then(|maps|plan_problem(maps, &images, classroom)) {
+            report.push(Severity::Error, name, reason);
+        }
+    }
+
+    for image in &images {
+        if !used_images.contains(image.name.as_str()) {
+            report.push(Severity::Warning, &image.name, "image is not used by any classroom".to_owned());
+        }
+    }
+    Ok(report)
+}
+
+/// Выдает, почему разметка аудитории не ложится на ее план этажа
+///
+/// # Примечание:
+/// Если плана нет в maps.json или его картинка не раскодируется, сверять не с чем
+fn plan_problem(maps: &MapCatalog, images: &[CampusImage], classroom: &ClassroomData) -> Option<String> {
+    let plan = classroom.plan.as_ref()?;
+    let floor_map = maps.room_plan(plan, &ClassroomLocation::parse(&classroom.classroom))?;
+    let image = images.iter().find(|x|x.name == floor_map.image)?;
+    let size = floor_plan::plan_size(&ImageResource::decode(image).ok()?.bytes).ok()?;
+    floor_plan::check_points(plan, size)
+        .err()
+        .map(|reason|format!("floor plan {}: {}", floor_map.id, reason))
+}
+
+fn is_placeholder(description: &str) -> bool {
+    let description = description.trim().trim_end_matches('.').to_lowercase().replace('ё', "е");
+    PLACEHOLDER_DESCRIPTIONS.contains(&description.as_str())
+}
+
+#[cfg(test)]
+mod tests{
+    use serde_json::json;
+    use super::*;
+
+    fn issues_for<'a>(report: &'a Report, subject: &str) -> Vec<&'a str> {
+        report.issues.iter().filter(|x|x.subject == subject).map(|x|x.message.as_str()).collect()
+    }
+
+    #[test]
+    fn test_validate_clean(){
+        let classrooms = json!([{"classroom": "УК3 104", "images": ["a.png"], "description": "Налево от лестницы"}]);
+        let images = json!([{"name": "a.png", "value": "iVBORw0KGgo="}]);
+        let report = validate_dataset(&classrooms.to_string(), &images.to_string(), None).unwrap();
+        assert!(report.issues.is_empty(), "{}", report);
+    }
+
+    #[test]
+    fn test_validate_problems(){
+        let classrooms = json!([
+            {"classroom": "УК3 104", "images": ["a.png", "typo.png"], "description": "Описание ещё не добавлено."},
+            {"classroom": "УК3 104", "images": ["a.png"], "description": "Налево"},
+            {"classroom": "УТК", "images": [], "description": " ", "plan": {"points": []}},
+        ]);
+        let images = json!([
+            {"name": "a.png", "value": "iVBORw0KGgo="},
+            {"name": "unused.png", "value": "bibabob!"},
+        ]);
+        let report = validate_dataset(&classrooms.to_string(), &images.to_string(), None).unwrap();
+        assert!(report.has_errors());
+        assert_eq!(report.count(Severity::Error), 4);
+        assert_eq!(issues_for(&report, "УК3 104"), vec![
+            "references missing image typo.png",
+            "placeholder description \"Описание ещё не добавлено.\"",
+            "duplicate classroom name",
+        ]);
+        assert_eq!(issues_for(&report, "УТК"), vec![
+            "no images",
+            "empty description",
+            "room name could not be parsed",
+            "floor plan has no points",
+        ]);
+        assert_eq!(issues_for(&report, "unused.png").len(), 2);
+    }
+
+    #[test]
+    fn test_validate_plan_bounds(){
+        use base64::Engine;
+        let mut plan = Vec::new();
+        image::RgbaImage::new(200, 100)
+            .write_to(&mut std::io::Cursor::new(&mut plan), image::ImageOutputFormat::Png)
+            .unwrap();
+        let classrooms = json!([
+            {"classroom": "УК3 204", "images": ["uk3-2.png"], "description": "Налево", "plan": {"points": [[150, 50]]}},
+            {"classroom": "УК3 205", "images": ["uk3-2.png"], "description": "Направо", "plan": {"points": [[250, 50]]}},
+        ]);
+        let images = json!([{"name": "uk3-2.png", "value": base64::engine::general_purpose::STANDARD.encode(&plan)}]);
+        let maps = json!([
+            {"id": "campus", "title": "Кампус", "kind": "campus", "image": "uk3-2.png"},
+            {"id": "uk3-2", "title": "УК3, 2 этаж", "kind": "floor", "building": "УК3", "floor": 2, "image": "uk3-2.png"},
+        ]);
+        let report = validate_dataset(&classrooms.to_string(), &images.to_string(), Some(&maps.to_string())).unwrap();
+        assert!(issues_for(&report, "УК3 204").is_empty(), "{}", report);
+        assert_eq!(issues_for(&report, "УК3 205"), vec!["floor plan uk3-2: Point [250, 50] is outside the 200x100 plan"]);
+        let report = validate_dataset(&classrooms.to_string(), &images.to_string(), None).unwrap();
+        assert!(!report.has_errors());
+        assert!(validate_dataset(&classrooms.to_string(), &images.to_string(), Some("[]")).is_err());
+    }
+
+    #[test]
+    fn test_validate_schema(){
+        assert!(validate_dataset("[{\"classroom\": 1}]", "[]", None).is_err());
+        assert!(validate_dataset("[]", "{}", None).is_err());
+    }
+}